@@ -1,4 +1,4 @@
-use opentransportdata::{get_train_formation, parse_formation_for_stop};
+use opentransportdata::{Operator, get_train_formation, parse_formation_for_stop};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
 
@@ -6,7 +6,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let token = std::env::var("FORMATION_TOKEN").expect("FORMATION_TOKEN not set");
 
-    let formation = get_train_formation(808,2026,2,7, &token).unwrap();
+    let formation = get_train_formation(&Operator::Sbb, 808,2026,2,7, &token).unwrap();
 
     println!("Train number: {}", formation.train_meta_information.train_number);
     // dbg!(&formation);
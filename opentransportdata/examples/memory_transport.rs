@@ -0,0 +1,117 @@
+use futures_util::StreamExt;
+use opentransportdata::{
+    DiscoveryQuery, FormationClient, HttpResponse, MemoryTransport, OjpClient, Operator, TrainId,
+};
+use std::fs;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let transport = MemoryTransport::new()
+        .route(
+            "trainNumber=825",
+            HttpResponse::ok(fs::read_to_string("test_data/test_response.json")?),
+        )
+        .route(
+            "OJPLocationInformationRequest",
+            HttpResponse::ok(fs::read_to_string(
+                "test_data/location_information_response.xml",
+            )?),
+        )
+        .route(
+            "OJPTripInfoRequest",
+            HttpResponse::ok(fs::read_to_string("test_data/trip_info_response.xml")?),
+        )
+        .route(
+            "OJPStopEventRequest",
+            HttpResponse::ok(fs::read_to_string("test_data/stop_event_response.xml")?),
+        );
+
+    let formations = FormationClient::with_transport(transport.clone(), "token");
+    let date = chrono::NaiveDate::from_ymd_opt(2026, 1, 31).unwrap();
+    let formation = formations
+        .get_train_formation(&Operator::Sbb, 825, date)
+        .await?;
+    println!(
+        "Train number: {}",
+        formation.train_meta_information.train_number
+    );
+
+    let mut batch = std::pin::pin!(
+        formations.fetch_formations(vec![TrainId::sbb(825), TrainId::sbb(999)], date)
+    );
+    while let Some((train, result)) = batch.next().await {
+        match result {
+            Ok(formation) => println!(
+                "Train {}: {} stops",
+                train,
+                formation.formations_at_scheduled_stops.len()
+            ),
+            Err(e) => println!("Train {}: {}", train, e),
+        }
+    }
+
+    let ojp = OjpClient::with_transport(transport.clone(), "token");
+    let query = DiscoveryQuery::new(date).add_hub(8503000, "Zürich HB");
+    println!("Train numbers: {:?}", ojp.discover(&query).await?);
+
+    let mut formation = formation;
+    for event in ojp.discover_events(&query).await?.events {
+        if event.train_id() == Some(formation.train_id()) {
+            formation.apply_realtime(&event);
+        }
+    }
+    for stop in formation.formations_at_scheduled_stops.iter() {
+        let time = &stop.scheduled_stop.stop_time;
+        if let Some(delay) = time.departure_delay().or(time.arrival_delay()) {
+            println!(
+                "{}: {:+} min",
+                stop.scheduled_stop.stop_point.name,
+                delay.num_minutes()
+            );
+        }
+    }
+
+    for station in ojp.find_stops("bern").await? {
+        println!("{} {} {:?}", station.uic, station.name, station.position);
+    }
+    // Answered from the cache filled by the search above.
+    println!("{:?}", ojp.stop_by_uic(8516161).await?.map(|s| s.name));
+
+    let journey = &formation.journey_meta_information.sjyid;
+    if let Some(trip) = ojp.trip_info(journey, date).await? {
+        println!("{}: {} calls", journey, trip.calls.len());
+        for discrepancy in trip.discrepancies(&formation) {
+            println!("  {}", discrepancy);
+        }
+    }
+
+    let hub = &query.hubs[0];
+    for event in ojp.stop_events(&query.stop_event_request(hub)).await? {
+        let departure = event.this_call.departure;
+        println!(
+            "{} {} nach {}: {} Gleis {}{}{}",
+            event.service.public_code.as_deref().unwrap_or("?"),
+            event.service.train_number.as_deref().unwrap_or("?"),
+            event.service.destination.as_deref().unwrap_or("?"),
+            departure
+                .map(|dep| dep.timetabled.to_string())
+                .unwrap_or_default(),
+            event.this_call.quay().unwrap_or("?"),
+            departure
+                .and_then(|dep| dep.delay())
+                .map(|delay| format!(" +{} min", delay.num_minutes()))
+                .unwrap_or_default(),
+            if event.service.cancelled {
+                " (fällt aus)"
+            } else {
+                ""
+            },
+        );
+    }
+
+    for request in transport.requests() {
+        println!("{:?} {}", request.method, request.url);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use opentransportdata::short_string::Node;
+use opentransportdata::{parse_formation_json, parse_short_string};
+use std::fs;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let json_data = fs::read_to_string("test_data/test_response.json")?;
+    let formation = parse_formation_json(&json_data)?;
+
+    for stop in formation.formations_at_scheduled_stops.iter() {
+        let raw = &stop.formation_short.formation_short_string;
+        let parsed = parse_short_string(raw)?;
+        assert_eq!(parsed.to_string(), *raw, "round trip failed");
+
+        println!("{}: {}", stop.scheduled_stop.stop_point.name, raw);
+        for (i, unit) in parsed.units().enumerate() {
+            let groups = unit
+                .nodes
+                .iter()
+                .filter(|node| matches!(node, Node::Group(_)))
+                .count();
+            println!(
+                "  unit {} at {}..{} with {} passage group(s)",
+                i, unit.span.start, unit.span.end, groups
+            );
+        }
+    }
+
+    Ok(())
+}
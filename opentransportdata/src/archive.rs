@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    FormationResponse, LineCatalog, RuleSet, StatusFlag, StopPoint, TrainId,
+    parse_formation_for_stop_with_rules,
+};
+
+/// A deklassiert coach, as seen on one train and operation day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sighting {
+    pub operation_date: NaiveDate,
+    pub train: TrainId,
+    /// Id of the catalog line the train runs on, e.g. `IC61`.
+    pub line: Option<String>,
+    pub evn: Option<String>,
+    pub order_number: Option<u32>,
+    /// First stop the coach is deklassiert at.
+    pub from: StopPoint,
+    /// Last stop the coach is deklassiert at.
+    pub to: StopPoint,
+}
+
+impl Sighting {
+    /// Identifies the coach within its train: the EVN, else the order
+    /// number.
+    pub fn coach(&self) -> String {
+        match (&self.evn, self.order_number) {
+            (Some(evn), _) => evn.clone(),
+            (None, Some(number)) => number.to_string(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Coaches of `formation` that `rules` flag as deklassiert, in order of the
+/// stop they are first seen at, with the train's line from `lines`.
+/// Pass-through stops are skipped.
+pub fn sightings(
+    formation: &FormationResponse,
+    operation_date: NaiveDate,
+    rules: &RuleSet,
+    lines: &LineCatalog,
+) -> Vec<Sighting> {
+    let train = formation.train_id();
+    let line = lines.line_for_train(formation).map(|line| line.id.clone());
+    let mut found: Vec<Sighting> = Vec::new();
+    for (index, stop) in formation.formations_at_scheduled_stops.iter().enumerate() {
+        if stop.scheduled_stop.stop_type.contains('D') {
+            continue;
+        }
+        let stop_point = &stop.scheduled_stop.stop_point;
+        for vehicle in parse_formation_for_stop_with_rules(formation, index, rules) {
+            if !vehicle.status.contains(&StatusFlag::Deklassiert) {
+                continue;
+            }
+            let evn = vehicle
+                .vehicle_identifier
+                .as_ref()
+                .and_then(|id| id.evn.clone());
+            let seen = found.iter_mut().find(|sighting| match &evn {
+                Some(_) => sighting.evn == evn,
+                None => sighting.evn.is_none() && sighting.order_number == vehicle.order_number,
+            });
+            match seen {
+                Some(sighting) => sighting.to = stop_point.clone(),
+                None => found.push(Sighting {
+                    operation_date,
+                    train: train.clone(),
+                    line: line.clone(),
+                    evn,
+                    order_number: vehicle.order_number,
+                    from: stop_point.clone(),
+                    to: stop_point.clone(),
+                }),
+            }
+        }
+    }
+    found
+}
+
+/// Sightings counted by weekday, train, line and month.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Statistics {
+    pub total: usize,
+    /// Distinct operation days with at least one sighting.
+    pub days: usize,
+    /// Monday first.
+    pub by_weekday: [usize; 7],
+    /// Operation days data was collected on, by weekday, Monday first.
+    pub observed_by_weekday: [usize; 7],
+    /// Most sightings first.
+    pub by_train: Vec<(TrainId, usize)>,
+    pub by_line: BTreeMap<String, usize>,
+    /// Keyed `2026-03`.
+    pub by_month: BTreeMap<String, usize>,
+}
+
+impl Statistics {
+    /// Count `sightings`, collected on the operation days `observed`. Days
+    /// with a sighting count as observed either way.
+    pub fn from_sightings(sightings: &[Sighting], observed: &[NaiveDate]) -> Self {
+        let mut stats = Statistics {
+            total: sightings.len(),
+            ..Statistics::default()
+        };
+        let mut days = BTreeSet::new();
+        let mut by_train: BTreeMap<&TrainId, usize> = BTreeMap::new();
+        for sighting in sightings {
+            let date = sighting.operation_date;
+            days.insert(date);
+            stats.by_weekday[date.weekday().num_days_from_monday() as usize] += 1;
+            *by_train.entry(&sighting.train).or_default() += 1;
+            if let Some(line) = &sighting.line {
+                *stats.by_line.entry(line.clone()).or_default() += 1;
+            }
+            *stats
+                .by_month
+                .entry(date.format("%Y-%m").to_string())
+                .or_default() += 1;
+        }
+        stats.days = days.len();
+        days.extend(observed.iter().copied());
+        for day in days {
+            stats.observed_by_weekday[day.weekday().num_days_from_monday() as usize] += 1;
+        }
+        stats.by_train = by_train
+            .into_iter()
+            .map(|(train, count)| (train.clone(), count))
+            .collect();
+        stats
+            .by_train
+            .sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        stats
+    }
+
+    /// Sightings per observed day of each weekday, Monday first, so
+    /// weekdays compare while data collection is uneven. `None` for a
+    /// weekday without observed days.
+    pub fn weekday_rates(&self) -> [Option<f64>; 7] {
+        std::array::from_fn(|i| {
+            let observed = self.observed_by_weekday[i];
+            (observed > 0).then(|| self.by_weekday[i] as f64 / observed as f64)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Formation, FormationVehicle, Operator, TrolleyStatus, VehicleIdentifier, VehicleProperties,
+        parse_formation_json,
+    };
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    fn vehicle(number: u32, evn: Option<&str>) -> FormationVehicle {
+        FormationVehicle {
+            vehicle_identifier: evn.map(|evn| VehicleIdentifier {
+                type_code: None,
+                type_code_name: None,
+                build_type_code: None,
+                country_code: None,
+                vehicle_number: None,
+                check_number: None,
+                evn: Some(evn.to_string()),
+                parent_evn: None,
+                position: None,
+            }),
+            position: number,
+            number,
+            formation_vehicle_at_scheduled_stops: Vec::new(),
+            vehicle_properties: Some(VehicleProperties {
+                trolley_status: Some(TrolleyStatus::Deklassiert),
+            }),
+        }
+    }
+
+    /// The fixture with coach 9 uncoupled after Thun, and coaches 9 and 4
+    /// reported deklassiert.
+    fn formation() -> FormationResponse {
+        let mut formation =
+            parse_formation_json(include_str!("../test_data/test_response.json")).unwrap();
+        for stop in formation.formations_at_scheduled_stops[4..].iter_mut() {
+            stop.formation_short.formation_short_string =
+                "@A,[(LK,1:1,1:2,1:3,W2:4,2:5,2:6,2:7,2:8)]".to_string();
+        }
+        formation.formations = vec![Formation {
+            formation_vehicles: vec![vehicle(9, None), vehicle(4, Some("508588940045"))],
+        }];
+        formation
+    }
+
+    #[test]
+    fn coaches_seen_over_a_stop_range() {
+        let found = sightings(
+            &formation(),
+            date("2026-01-31"),
+            RuleSet::builtin(),
+            LineCatalog::builtin(),
+        );
+        assert_eq!(found.len(), 2);
+        let uncoupled = &found[0];
+        assert_eq!(uncoupled.train, TrainId::new(Operator::Sbb, 825));
+        assert_eq!(uncoupled.operation_date, date("2026-01-31"));
+        assert_eq!(uncoupled.line.as_deref(), Some("IC8"));
+        assert_eq!(uncoupled.evn, None);
+        assert_eq!(uncoupled.coach(), "9");
+        assert_eq!(uncoupled.from.name, "Brig");
+        assert_eq!(uncoupled.to.name, "Thun");
+        let throughout = &found[1];
+        assert_eq!(throughout.coach(), "508588940045");
+        assert_eq!(throughout.from.name, "Brig");
+        assert_eq!(throughout.to.name, "Romanshorn");
+
+        // Stops the train passes without stopping do not count.
+        let mut formation = formation();
+        for stop in [0, 3] {
+            formation.formations_at_scheduled_stops[stop]
+                .scheduled_stop
+                .stop_type = "D".to_string();
+        }
+        let found = sightings(
+            &formation,
+            date("2026-01-31"),
+            RuleSet::builtin(),
+            LineCatalog::builtin(),
+        );
+        assert_eq!(found[0].from.name, "Visp");
+        assert_eq!(found[0].to.name, "Spiez");
+    }
+
+    fn sighting(operation_date: &str, train_number: i32, line: Option<&str>) -> Sighting {
+        let stop = StopPoint {
+            uic: 8507000,
+            name: "Bern".to_string(),
+        };
+        Sighting {
+            operation_date: date(operation_date),
+            train: TrainId::new(Operator::Sbb, train_number),
+            line: line.map(str::to_string),
+            evn: None,
+            order_number: Some(4),
+            from: stop.clone(),
+            to: stop,
+        }
+    }
+
+    #[test]
+    fn statistics() {
+        let sightings = [
+            // Fridays.
+            sighting("2026-01-30", 825, Some("IC8")),
+            sighting("2026-01-30", 965, Some("IC61")),
+            sighting("2026-02-06", 825, Some("IC8")),
+            // A Monday.
+            sighting("2026-02-02", 825, None),
+        ];
+        let observed = [
+            date("2026-01-26"),
+            date("2026-01-30"),
+            date("2026-02-02"),
+            date("2026-02-09"),
+            date("2026-02-16"),
+        ];
+        let stats = Statistics::from_sightings(&sightings, &observed);
+        assert_eq!(stats.total, 4);
+        assert_eq!(stats.days, 3);
+        assert_eq!(stats.by_weekday, [1, 0, 0, 0, 3, 0, 0]);
+        assert_eq!(stats.observed_by_weekday, [4, 0, 0, 0, 2, 0, 0]);
+        assert_eq!(
+            stats.weekday_rates(),
+            [Some(0.25), None, None, None, Some(1.5), None, None]
+        );
+        assert_eq!(
+            stats.by_train,
+            vec![
+                (TrainId::new(Operator::Sbb, 825), 3),
+                (TrainId::new(Operator::Sbb, 965), 1)
+            ]
+        );
+        assert_eq!(
+            stats.by_line,
+            BTreeMap::from([("IC61".to_string(), 1), ("IC8".to_string(), 2)])
+        );
+        assert_eq!(
+            stats.by_month,
+            BTreeMap::from([("2026-01".to_string(), 2), ("2026-02".to_string(), 2)])
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{OtdError, Vehicle, VehicleType};
+
+const DEFAULT_CATALOG: &str = include_str!("../catalog/rolling_stock.toml");
+
+static DEFAULT_ROLLING_STOCK: LazyLock<RollingStockCatalog> = LazyLock::new(|| {
+    RollingStockCatalog::from_toml(DEFAULT_CATALOG).expect("built-in catalog is valid")
+});
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Family {
+    Ic2000,
+    /// Refurbished IC2000.
+    Ic2020,
+    EwIv,
+    Re460,
+    Other(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct StockMatch {
+    #[serde(default)]
+    pub type_code: Vec<u32>,
+    #[serde(default)]
+    pub type_code_name: Vec<String>,
+    #[serde(default)]
+    pub build_type_code: Vec<String>,
+    /// EVN digits 5-8, see [`crate::Evn::type_code`].
+    #[serde(default)]
+    pub evn_type_code: Vec<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RollingStock {
+    /// Display name, e.g. "EW IV Bt".
+    pub name: String,
+    pub family: Family,
+    #[serde(default)]
+    pub class: Option<u8>,
+    #[serde(default)]
+    pub double_deck: bool,
+    /// Has a driver's cab (Steuerwagen).
+    #[serde(default)]
+    pub driving_trailer: bool,
+    #[serde(default)]
+    pub restaurant: bool,
+    #[serde(default)]
+    pub traction: bool,
+    #[serde(rename = "match", default)]
+    pub matches: Vec<StockMatch>,
+}
+
+/// Which end of a vehicle, as drawn from left to right, has the driver's cab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CabEnd {
+    Left,
+    Right,
+}
+
+/// Maps vehicle identifiers to rolling stock families.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RollingStockCatalog {
+    #[serde(rename = "vehicle", default)]
+    pub vehicles: Vec<RollingStock>,
+}
+
+impl Default for RollingStockCatalog {
+    /// The entries in `catalog/rolling_stock.toml`.
+    fn default() -> Self {
+        DEFAULT_ROLLING_STOCK.clone()
+    }
+}
+
+impl RollingStockCatalog {
+    pub fn from_toml(input: &str) -> Result<Self, OtdError> {
+        toml::from_str(input).map_err(|e| OtdError::Config(e.to_string()))
+    }
+
+    /// The built-in catalog, without cloning it.
+    pub fn builtin() -> &'static RollingStockCatalog {
+        &DEFAULT_ROLLING_STOCK
+    }
+
+    /// First entry matching the vehicle's identifier. `None` if the vehicle
+    /// has no identifier or nothing matches.
+    pub fn identify(&self, vehicle: &Vehicle) -> Option<&RollingStock> {
+        vehicle.vehicle_identifier.as_ref()?;
+        self.vehicles
+            .iter()
+            .find(|stock| stock.matches.iter().any(|m| m.matches(vehicle)))
+    }
+
+    /// Cab end of `vehicles[index]` if it is a driving trailer. The cab faces
+    /// away from the rest of its traction unit, or of the train if the
+    /// formation string has no `[...]` units.
+    pub fn cab_end(&self, vehicles: &[Vehicle], index: usize) -> Option<CabEnd> {
+        if !self.identify(vehicles.get(index)?)?.driving_trailer {
+            return None;
+        }
+        unit_end(vehicles, index)
+    }
+}
+
+/// Which end of its traction unit `vehicles[index]` is at; `None` in the
+/// middle. Fictional and parked vehicles are not counted.
+pub fn unit_end(vehicles: &[Vehicle], index: usize) -> Option<CabEnd> {
+    let unit = vehicles.get(index)?.traction_unit;
+    let in_unit = |vehicle: &&Vehicle| vehicle.traction_unit == unit;
+    let before = vehicles[..index].iter().rev().find(|v| !is_placeholder(v));
+    let after = vehicles[index + 1..].iter().find(|v| !is_placeholder(v));
+    match (before.filter(in_unit), after.filter(in_unit)) {
+        (None, _) => Some(CabEnd::Left),
+        (_, None) => Some(CabEnd::Right),
+        _ => None,
+    }
+}
+
+fn is_placeholder(vehicle: &Vehicle) -> bool {
+    matches!(
+        vehicle.vehicle_type,
+        VehicleType::Fictional | VehicleType::Parked
+    )
+}
+
+impl StockMatch {
+    fn matches(&self, vehicle: &Vehicle) -> bool {
+        let Some(id) = vehicle.vehicle_identifier.as_ref() else {
+            return false;
+        };
+        if !self.type_code.is_empty()
+            && !id
+                .type_code
+                .is_some_and(|code| self.type_code.contains(&code))
+        {
+            return false;
+        }
+        if !self.type_code_name.is_empty()
+            && !id
+                .type_code_name
+                .as_ref()
+                .is_some_and(|name| self.type_code_name.contains(name))
+        {
+            return false;
+        }
+        if !self.build_type_code.is_empty()
+            && !id
+                .build_type_code
+                .as_ref()
+                .is_some_and(|code| self.build_type_code.contains(code))
+        {
+            return false;
+        }
+        if !self.evn_type_code.is_empty()
+            && !matches!(id.parsed_evn(), Some(Ok(evn)) if self.evn_type_code.contains(&evn.type_code))
+        {
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VehicleIdentifier, parse_short_string};
+
+    fn identified(formation: &str, identifiers: &[(&str, &str)]) -> Vec<Vehicle> {
+        let mut vehicles = parse_short_string(formation).unwrap().to_vehicles();
+        let mut identifiers = identifiers.iter();
+        for vehicle in vehicles
+            .iter_mut()
+            .filter(|v| v.vehicle_type != VehicleType::Fictional)
+        {
+            let Some((name, evn)) = identifiers.next() else {
+                break;
+            };
+            vehicle.vehicle_identifier = Some(VehicleIdentifier {
+                type_code: None,
+                type_code_name: Some(name.to_string()),
+                build_type_code: None,
+                country_code: None,
+                vehicle_number: None,
+                check_number: None,
+                evn: Some(evn.to_string()),
+                parent_evn: None,
+                position: None,
+            });
+        }
+        vehicles
+    }
+
+    fn names(catalog: &RollingStockCatalog, vehicles: &[Vehicle]) -> Vec<Option<String>> {
+        vehicles
+            .iter()
+            .map(|v| catalog.identify(v).map(|stock| stock.name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn tells_ic2000_from_ew_iv_by_evn() {
+        let catalog = RollingStockCatalog::default();
+        // Same type code names, and the EW IV coach carries `NF` here.
+        let vehicles = identified(
+            "[2:1#NF,2:2#NF,1:3]",
+            &[
+                ("B", "50 85 26-33 021"),
+                ("B", "50 85 20-94 101"),
+                ("A", "50 85 19-94 012"),
+            ],
+        );
+        assert_eq!(
+            names(&catalog, &vehicles),
+            vec![
+                Some("IC2000 B".to_string()),
+                Some("EW IV B".to_string()),
+                Some("EW IV A".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_series_is_not_guessed() {
+        let catalog = RollingStockCatalog::default();
+        let vehicles = identified("2:1#NF", &[("B", "50 85 99-99 001")]);
+        assert_eq!(names(&catalog, &vehicles), vec![None]);
+        assert!(catalog.identify(&identified("2:1", &[])[0]).is_none());
+    }
+
+    #[test]
+    fn cab_faces_away_from_its_unit() {
+        let catalog = RollingStockCatalog::default();
+        let vehicles = identified(
+            "F,[2:1,2:2,LK],[LK,2:3,2:4],F",
+            &[
+                ("Bt", "50 85 28-94 901"),
+                ("B", "50 85 20-94 102"),
+                ("Re 460", "91 85 4460 001"),
+                ("Re 460", "91 85 4460 002"),
+                ("B", "50 85 20-94 103"),
+                ("Bt", "50 85 28-94 902"),
+            ],
+        );
+        let ends: Vec<_> = (0..vehicles.len())
+            .map(|i| catalog.cab_end(&vehicles, i))
+            .collect();
+        assert_eq!(
+            ends,
+            vec![
+                None,
+                Some(CabEnd::Left),
+                None,
+                None,
+                None,
+                None,
+                Some(CabEnd::Right),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn unit_end_without_brackets_uses_train_ends() {
+        let vehicles = parse_short_string("F,2:1,2:2,2:3,F").unwrap().to_vehicles();
+        assert_eq!(unit_end(&vehicles, 1), Some(CabEnd::Left));
+        assert_eq!(unit_end(&vehicles, 2), None);
+        assert_eq!(unit_end(&vehicles, 3), Some(CabEnd::Right));
+    }
+}
@@ -0,0 +1,702 @@
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use futures_util::stream::{self, Stream, StreamExt};
+
+#[cfg(feature = "native-client")]
+use crate::ReqwestTransport;
+use crate::discovery::{Discovered, DiscoveryQuery, merge_train_numbers};
+use crate::rate_limit::{Budget, RateLimiter};
+use crate::request_stats::{RequestCounts, RequestStats};
+use crate::stations::best_match;
+use crate::transport::{HttpRequest, HttpResponse, Transport};
+use crate::{
+    FormationResponse, FormationUpdate, LocationInformationRequest, Operator, OtdError, Station,
+    StationCache, StopEvent, StopEventRequest, TrainId, TripInfo, TripInfoRequest, UpdateTracker,
+    parse_formation_json, parse_stations, parse_stop_events, parse_trip_info,
+};
+
+pub const FORMATION_BASE_URL: &str = "https://api.opentransportdata.swiss/formation/v2";
+pub const OJP_URL: &str = "https://api.opentransportdata.swiss/ojp20";
+
+/// Pause after a `429` that came without `Retry-After`.
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub base_url: String,
+    pub timeout: Duration,
+    pub user_agent: String,
+    pub rate_limiter: RateLimiter,
+    /// How often a `429` is retried before it is returned to the caller.
+    pub max_retries: u32,
+    pub request_stats: RequestStats,
+}
+
+impl ClientConfig {
+    fn new(base_url: &str, rate_limiter: RateLimiter) -> Self {
+        ClientConfig {
+            base_url: base_url.to_string(),
+            timeout: Duration::from_secs(30),
+            user_agent: concat!("opentransportdata/", env!("CARGO_PKG_VERSION")).to_string(),
+            rate_limiter,
+            max_retries: 3,
+            request_stats: RequestStats::new(),
+        }
+    }
+
+    fn prepare(&self, request: HttpRequest) -> HttpRequest {
+        let mut request = request.header("User-Agent", self.user_agent.as_str());
+        request.timeout = Some(self.timeout);
+        request
+    }
+
+    /// Send `request` under the rate limiter, pausing it for any `Retry-After`
+    /// and retrying `429`s.
+    async fn execute<T: Transport>(
+        &self,
+        transport: &T,
+        request: HttpRequest,
+    ) -> Result<HttpResponse, OtdError> {
+        let request = self.prepare(request);
+        let mut attempt = 0;
+        loop {
+            self.rate_limiter.acquire().await;
+            let response = match transport.send(request.clone()).await {
+                Ok(response) => response,
+                Err(e) => {
+                    self.request_stats.record_transport_error();
+                    return Err(e);
+                }
+            };
+            self.request_stats.record_response(response.status);
+            let result = response.error_for_status();
+            if let Err(OtdError::Http {
+                status,
+                retry_after,
+            }) = &result
+            {
+                if let Some(wait) = retry_after {
+                    self.rate_limiter.pause_for(*wait);
+                }
+                if *status == 429 && attempt < self.max_retries {
+                    if retry_after.is_none() {
+                        self.rate_limiter.pause_for(DEFAULT_BACKOFF);
+                    }
+                    attempt += 1;
+                    continue;
+                }
+            }
+            return result;
+        }
+    }
+}
+
+/// Async client for the formation API.
+#[derive(Debug, Clone)]
+pub struct FormationClient<T> {
+    transport: T,
+    token: String,
+    config: ClientConfig,
+    /// Requests in flight at once in [`FormationClient::fetch_formations`].
+    concurrency: usize,
+    updates: UpdateTracker,
+}
+
+#[cfg(feature = "native-client")]
+impl FormationClient<ReqwestTransport> {
+    pub fn new(token: impl Into<String>) -> Self {
+        FormationClient::with_transport(ReqwestTransport::new(), token)
+    }
+}
+
+impl<T: Transport> FormationClient<T> {
+    /// The client starts with its own limiter of 5 requests per minute.
+    pub fn with_transport(transport: T, token: impl Into<String>) -> Self {
+        FormationClient {
+            transport,
+            token: token.into(),
+            config: ClientConfig::new(FORMATION_BASE_URL, RateLimiter::per_minute(5)),
+            concurrency: 4,
+            updates: UpdateTracker::new(),
+        }
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.config.base_url = base_url.into();
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.config.user_agent = user_agent.into();
+        self
+    }
+
+    /// Share `rate_limiter` with other clients of the same endpoint.
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.config.rate_limiter = rate_limiter;
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.config.max_retries = max_retries;
+        self
+    }
+
+    /// Share `request_stats` with other clients of the same endpoint.
+    pub fn request_stats(mut self, request_stats: RequestStats) -> Self {
+        self.config.request_stats = request_stats;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Share `tracker` with other clients, or seed it with restored trains.
+    pub fn update_tracker(mut self, tracker: UpdateTracker) -> Self {
+        self.updates = tracker;
+        self
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.config.rate_limiter.budget()
+    }
+
+    pub fn requests(&self) -> RequestCounts {
+        self.config.request_stats.counts()
+    }
+
+    pub fn updates(&self) -> &UpdateTracker {
+        &self.updates
+    }
+
+    fn formation_url(&self, operator: &Operator, train_number: i32, date: NaiveDate) -> String {
+        format!(
+            "{}/formations_full?evu={}&operationDate={}&trainNumber={}",
+            self.config.base_url,
+            operator,
+            date.format("%Y-%m-%d"),
+            train_number
+        )
+    }
+
+    pub async fn get_train_formation(
+        &self,
+        operator: &Operator,
+        train_number: i32,
+        operation_date: NaiveDate,
+    ) -> Result<FormationResponse, OtdError> {
+        let url = self.formation_url(operator, train_number, operation_date);
+        let request = HttpRequest::get(url).header("Authorization", self.token.as_str());
+        let response = self.config.execute(&self.transport, request).await?;
+        let mut formation = parse_formation_json(&response.body)?;
+        formation.operator = operator.clone();
+        Ok(formation)
+    }
+
+    /// Like [`FormationClient::get_train_formation`], but sends the
+    /// validators of the last fetch and reports whether the formation
+    /// changed since.
+    pub async fn get_formation_update(
+        &self,
+        train: &TrainId,
+        operation_date: NaiveDate,
+    ) -> Result<FormationUpdate, OtdError> {
+        let url = self.formation_url(&train.operator, train.train_number, operation_date);
+        let mut request = HttpRequest::get(url).header("Authorization", self.token.as_str());
+        if let Some(validators) = self.updates.get(train, operation_date) {
+            if let Some(etag) = validators.etag {
+                request = request.header("If-None-Match", etag);
+            }
+            if let Some(last_modified) = validators.last_modified {
+                request = request.header("If-Modified-Since", last_modified);
+            }
+        }
+        let response = match self.config.execute(&self.transport, request).await {
+            Err(OtdError::Http { status: 304, .. }) => return Ok(FormationUpdate::Unchanged),
+            result => result?,
+        };
+        let mut formation = parse_formation_json(&response.body)?;
+        formation.operator = train.operator.clone();
+        Ok(self.updates.record(formation, operation_date, &response))
+    }
+
+    /// Fetch many trains under the shared rate limiter. Results arrive as
+    /// they complete, not in input order, and a failing train does not stop
+    /// the others.
+    pub fn fetch_formations<I>(
+        &self,
+        trains: I,
+        operation_date: NaiveDate,
+    ) -> impl Stream<Item = (TrainId, Result<FormationResponse, OtdError>)> + '_
+    where
+        I: IntoIterator<Item = TrainId>,
+        I::IntoIter: 'static,
+    {
+        stream::iter(trains)
+            .map(move |train| async move {
+                let result = self
+                    .get_train_formation(&train.operator, train.train_number, operation_date)
+                    .await;
+                (train, result)
+            })
+            .buffer_unordered(self.concurrency)
+    }
+
+    /// [`FormationClient::fetch_formations`] with
+    /// [`FormationClient::get_formation_update`].
+    pub fn fetch_updates<I>(
+        &self,
+        trains: I,
+        operation_date: NaiveDate,
+    ) -> impl Stream<Item = (TrainId, Result<FormationUpdate, OtdError>)> + '_
+    where
+        I: IntoIterator<Item = TrainId>,
+        I::IntoIter: 'static,
+    {
+        stream::iter(trains)
+            .map(move |train| async move {
+                let result = self.get_formation_update(&train, operation_date).await;
+                (train, result)
+            })
+            .buffer_unordered(self.concurrency)
+    }
+}
+
+/// Async client for the OJP 2.0 API.
+#[derive(Debug, Clone)]
+pub struct OjpClient<T> {
+    transport: T,
+    token: String,
+    config: ClientConfig,
+    stations: StationCache,
+}
+
+#[cfg(feature = "native-client")]
+impl OjpClient<ReqwestTransport> {
+    pub fn new(token: impl Into<String>) -> Self {
+        OjpClient::with_transport(ReqwestTransport::new(), token)
+    }
+}
+
+impl<T: Transport> OjpClient<T> {
+    /// The client starts with its own limiter of 50 requests per minute.
+    pub fn with_transport(transport: T, token: impl Into<String>) -> Self {
+        OjpClient {
+            transport,
+            token: token.into(),
+            config: ClientConfig::new(OJP_URL, RateLimiter::per_minute(50)),
+            stations: StationCache::new(),
+        }
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.config.base_url = base_url.into();
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.config.user_agent = user_agent.into();
+        self
+    }
+
+    /// Share `rate_limiter` with other clients of the same endpoint.
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.config.rate_limiter = rate_limiter;
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.config.max_retries = max_retries;
+        self
+    }
+
+    /// Share `request_stats` with other clients of the same endpoint.
+    pub fn request_stats(mut self, request_stats: RequestStats) -> Self {
+        self.config.request_stats = request_stats;
+        self
+    }
+
+    /// Share `cache` with other clients.
+    pub fn station_cache(mut self, cache: StationCache) -> Self {
+        self.stations = cache;
+        self
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.config.rate_limiter.budget()
+    }
+
+    pub fn requests(&self) -> RequestCounts {
+        self.config.request_stats.counts()
+    }
+
+    /// POST an OJP request document and return the response XML.
+    pub async fn post(&self, xml: String) -> Result<String, OtdError> {
+        let request = HttpRequest::post(self.config.base_url.as_str(), xml)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .header("Content-Type", "application/xml");
+        let response = self.config.execute(&self.transport, request).await?;
+        Ok(response.body)
+    }
+
+    /// Departures and/or arrivals for `request`.
+    pub async fn stop_events(
+        &self,
+        request: &StopEventRequest,
+    ) -> Result<Vec<StopEvent>, OtdError> {
+        let xml = self.post(request.to_xml()).await?;
+        parse_stop_events(&xml)
+    }
+
+    /// All calls of a journey, e.g. [`crate::Service::journey_ref`] or a
+    /// formation's SJYID. `None` if OJP does not know the journey.
+    pub async fn trip_info(
+        &self,
+        journey_ref: &str,
+        operating_day: NaiveDate,
+    ) -> Result<Option<TripInfo>, OtdError> {
+        let request = TripInfoRequest::new(journey_ref, operating_day);
+        let xml = self.post(request.to_xml()).await?;
+        parse_trip_info(&xml)
+    }
+
+    /// Stops matching `name`, best match first. Cached per search text.
+    pub async fn find_stops(&self, name: &str) -> Result<Vec<Station>, OtdError> {
+        if let Some(stations) = self.stations.search(name) {
+            return Ok(stations);
+        }
+        let xml = self
+            .post(LocationInformationRequest::by_name(name).to_xml())
+            .await?;
+        let stations = parse_stations(&xml)?;
+        self.stations.insert_search(name, &stations);
+        Ok(stations)
+    }
+
+    /// The stop with UIC number `uic`, from the cache if it was seen before.
+    pub async fn stop_by_uic(&self, uic: u32) -> Result<Option<Station>, OtdError> {
+        if let Some(station) = self.stations.get(uic) {
+            return Ok(Some(station));
+        }
+        let xml = self
+            .post(LocationInformationRequest::by_uic(uic).to_xml())
+            .await?;
+        let station = parse_stations(&xml)?
+            .into_iter()
+            .find(|station| station.uic == uic);
+        if let Some(station) = station.as_ref() {
+            self.stations.insert(station.clone());
+        }
+        Ok(station)
+    }
+
+    /// Resolve a configured station name: the result named exactly `name`,
+    /// else the best match.
+    pub async fn station_by_name(&self, name: &str) -> Result<Option<Station>, OtdError> {
+        Ok(best_match(name, self.find_stops(name).await?))
+    }
+
+    /// Numbers of trains on the built-in lines departing from Bern today.
+    pub async fn fetch_train_numbers(&self) -> Result<Vec<i32>, OtdError> {
+        self.discover(&DiscoveryQuery::today()).await
+    }
+
+    /// Train numbers matching `query`, merged over all its hubs.
+    pub async fn discover(&self, query: &DiscoveryQuery) -> Result<Vec<i32>, OtdError> {
+        let discovered = self.discover_events(query).await?;
+        let trains = discovered
+            .events
+            .iter()
+            .filter_map(StopEvent::train_number)
+            .collect();
+        Ok(merge_train_numbers([trains]))
+    }
+
+    /// Stop events matching `query` at all its hubs. A train passing several
+    /// hubs has the event of the first.
+    pub async fn discover_events(&self, query: &DiscoveryQuery) -> Result<Discovered, OtdError> {
+        let mut discovered = Discovered::default();
+        for hub in query.hubs.iter() {
+            let xml = self.post(query.request_body(hub)).await?;
+            discovered.merge(query.stop_events(&xml)?);
+        }
+        Ok(discovered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LineCatalog, MemoryTransport};
+
+    const FORMATION_JSON: &str = include_str!("../test_data/test_response.json");
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 1, 31).unwrap()
+    }
+
+    fn ojp_transport() -> MemoryTransport {
+        MemoryTransport::new()
+            .route(
+                "OJPLocationInformationRequest",
+                HttpResponse::ok(include_str!(
+                    "../test_data/location_information_response.xml"
+                )),
+            )
+            .route(
+                "OJPTripInfoRequest",
+                HttpResponse::ok(include_str!("../test_data/trip_info_response.xml")),
+            )
+            .route(
+                "OJPStopEventRequest",
+                HttpResponse::ok(include_str!("../test_data/stop_event_response.xml")),
+            )
+    }
+
+    #[tokio::test]
+    async fn formation_from_fixture() {
+        let transport =
+            MemoryTransport::new().route("trainNumber=825", HttpResponse::ok(FORMATION_JSON));
+        let client = FormationClient::with_transport(transport.clone(), "token")
+            .base_url("http://formation.test");
+        let formation = client
+            .get_train_formation(&Operator::Sbb, 825, date())
+            .await
+            .unwrap();
+        assert_eq!(formation.train_id(), TrainId::sbb(825));
+        assert_eq!(formation.formations_at_scheduled_stops.len(), 12);
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].url,
+            "http://formation.test/formations_full?evu=SBBP&operationDate=2026-01-31&trainNumber=825"
+        );
+        assert!(
+            requests[0]
+                .headers
+                .contains(&("Authorization".to_string(), "token".to_string()))
+        );
+        assert_eq!(client.requests().by_status.get(&200), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn unknown_train_is_an_http_error() {
+        let client = FormationClient::with_transport(MemoryTransport::new(), "token");
+        let result = client
+            .get_train_formation(&Operator::Sbb, 999, date())
+            .await;
+        assert!(matches!(result, Err(OtdError::Http { status: 404, .. })));
+        assert_eq!(client.requests().errors(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_after_429() {
+        let transport = MemoryTransport::new()
+            .route_once(
+                "trainNumber=825",
+                HttpResponse::with_status(429, "").with_header("Retry-After", "20"),
+            )
+            .route("trainNumber=825", HttpResponse::ok(FORMATION_JSON));
+        let client = FormationClient::with_transport(transport.clone(), "token");
+        let start = tokio::time::Instant::now();
+        let formation = client
+            .get_train_formation(&Operator::Sbb, 825, date())
+            .await
+            .unwrap();
+        assert_eq!(formation.train_meta_information.train_number, 825);
+        assert_eq!(transport.requests().len(), 2);
+        assert!(start.elapsed() >= Duration::from_secs(20));
+
+        let counts = client.requests();
+        assert_eq!(counts.by_status.get(&429), Some(&1));
+        assert_eq!(counts.last_status, Some(200));
+        assert_eq!(client.config().rate_limiter.stats().waits, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_max_retries() {
+        let transport =
+            MemoryTransport::new().route("trainNumber", HttpResponse::with_status(429, ""));
+        let client = FormationClient::with_transport(transport.clone(), "token").max_retries(2);
+        let start = tokio::time::Instant::now();
+        let result = client
+            .get_train_formation(&Operator::Sbb, 825, date())
+            .await;
+        assert!(matches!(
+            result,
+            Err(OtdError::Http {
+                status: 429,
+                retry_after: None
+            })
+        ));
+        assert_eq!(transport.requests().len(), 3);
+        // Without `Retry-After` every retry waits the default backoff.
+        assert!(start.elapsed() >= DEFAULT_BACKOFF * 2);
+    }
+
+    #[tokio::test]
+    async fn not_modified_is_unchanged() {
+        let transport = MemoryTransport::new()
+            .route_once(
+                "trainNumber=825",
+                HttpResponse::ok(FORMATION_JSON).with_header("ETag", "\"v1\""),
+            )
+            .route("trainNumber=825", HttpResponse::with_status(304, ""));
+        let client = FormationClient::with_transport(transport.clone(), "token");
+        let train = TrainId::sbb(825);
+
+        let first = client.get_formation_update(&train, date()).await.unwrap();
+        assert!(first.is_changed());
+        let second = client.get_formation_update(&train, date()).await.unwrap();
+        assert_eq!(second, FormationUpdate::Unchanged);
+
+        let requests = transport.requests();
+        assert!(
+            requests[1]
+                .headers
+                .contains(&("If-None-Match".to_string(), "\"v1\"".to_string()))
+        );
+        let validators = client.updates().get(&train, date()).unwrap();
+        assert_eq!(validators.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(client.requests().last_status, Some(304));
+        assert!(client.requests().last_success.is_some());
+    }
+
+    #[tokio::test]
+    async fn same_last_update_is_unchanged() {
+        let transport =
+            MemoryTransport::new().route("trainNumber=825", HttpResponse::ok(FORMATION_JSON));
+        let client = FormationClient::with_transport(transport, "token");
+        let train = TrainId::sbb(825);
+        assert!(
+            client
+                .get_formation_update(&train, date())
+                .await
+                .unwrap()
+                .is_changed()
+        );
+        assert_eq!(
+            client.get_formation_update(&train, date()).await.unwrap(),
+            FormationUpdate::Unchanged
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_formations_reports_each_train() {
+        let transport =
+            MemoryTransport::new().route("trainNumber=825", HttpResponse::ok(FORMATION_JSON));
+        let client = FormationClient::with_transport(transport, "token");
+        let mut results: Vec<_> = client
+            .fetch_formations(vec![TrainId::sbb(825), TrainId::sbb(999)], date())
+            .collect()
+            .await;
+        results.sort_by_key(|(train, _)| train.train_number);
+        assert!(results[0].1.is_ok());
+        assert!(matches!(
+            results[1].1,
+            Err(OtdError::Http { status: 404, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn discovers_trains_from_fixture() {
+        let transport = ojp_transport();
+        let client = OjpClient::with_transport(transport.clone(), "token");
+        let query = DiscoveryQuery::new(date());
+        assert_eq!(client.discover(&query).await.unwrap(), vec![825, 965]);
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(
+            requests[0]
+                .headers
+                .contains(&("Authorization".to_string(), "Bearer token".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn unreadable_results_are_reported() {
+        let xml = include_str!("../test_data/stop_event_response.xml").replacen(
+            "<OperatingDayRef>2026-01-31</OperatingDayRef>",
+            "<OperatingDayRef>heute</OperatingDayRef>",
+            1,
+        );
+        let transport = MemoryTransport::new().route("OJPStopEventRequest", HttpResponse::ok(xml));
+        let client = OjpClient::with_transport(transport, "token");
+        let discovered = client
+            .discover_events(&DiscoveryQuery::new(date()))
+            .await
+            .unwrap();
+        assert_eq!(discovered.skipped.len(), 1);
+        assert!(!discovered.events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_operator_from_line_catalog() {
+        let xml = include_str!("../test_data/stop_event_response.xml").replace(
+            "<siri:OperatorRef>11</siri:OperatorRef>",
+            "<siri:OperatorRef>ch:1:sboid:100001</siri:OperatorRef>",
+        );
+        let transport = MemoryTransport::new().route("OJPStopEventRequest", HttpResponse::ok(xml));
+        let client = OjpClient::with_transport(transport, "token");
+        let query = DiscoveryQuery::new(date());
+        assert_eq!(client.discover(&query).await.unwrap(), vec![825, 965]);
+
+        // A BLS line with the same numbers makes the operator ambiguous.
+        let mut lines = LineCatalog::builtin().clone();
+        let mut bls = lines.by_id("IC8").unwrap().clone();
+        bls.operator = Operator::Bls;
+        lines.lines.push(bls);
+        let discovered = client.discover_events(&query.lines(lines)).await.unwrap();
+        assert_eq!(discovered.events.len(), 1);
+        assert_eq!(discovered.unidentified.len(), 1);
+        assert_eq!(discovered.unidentified[0].train_number(), Some(825));
+    }
+
+    #[tokio::test]
+    async fn trip_info_from_fixture() {
+        let client = OjpClient::with_transport(ojp_transport(), "token");
+        let trip = client
+            .trip_info("ch:1:sjyid:100001:825-001", date())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(trip.calls.len(), 12);
+    }
+
+    #[tokio::test]
+    async fn stations_are_cached() {
+        let transport = ojp_transport();
+        let client = OjpClient::with_transport(transport.clone(), "token");
+        let stations = client.find_stops("bern").await.unwrap();
+        assert_eq!(stations[0].uic, 8507000);
+        assert_eq!(stations[0].name, "Bern");
+
+        client.find_stops("bern").await.unwrap();
+        let wankdorf = client.stop_by_uic(8516161).await.unwrap().unwrap();
+        assert_eq!(wankdorf.name, "Bern Wankdorf");
+        assert_eq!(transport.requests().len(), 1);
+    }
+}
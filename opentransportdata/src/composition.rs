@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{
+    FormationResponse, StatusFlag, StopPoint, Vehicle, VehicleType, parse_formation_for_stop,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeKind {
+    Coupled,
+    Uncoupled,
+    Closed,
+    Reopened,
+    Reclassified {
+        from: VehicleType,
+        to: VehicleType,
+    },
+    /// Moved within the train; positions are 1-based among the vehicles
+    /// running on both legs.
+    Reordered {
+        from: usize,
+        to: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct VehicleChange {
+    pub kind: ChangeKind,
+    /// The vehicle as it is at the later stop, or at the earlier one if it
+    /// was uncoupled.
+    pub vehicle: Vehicle,
+}
+
+impl VehicleChange {
+    /// Locomotives and other vehicles without seats are not coaches.
+    pub fn is_coach(&self) -> bool {
+        !matches!(
+            self.vehicle.vehicle_type,
+            VehicleType::Locomotive | VehicleType::BaggageCar
+        )
+    }
+}
+
+impl fmt::Display for VehicleChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ChangeKind::Coupled => write!(f, "coupled")?,
+            ChangeKind::Uncoupled => write!(f, "uncoupled")?,
+            ChangeKind::Closed => write!(f, "closed")?,
+            ChangeKind::Reopened => write!(f, "reopened")?,
+            ChangeKind::Reclassified { from, to } => {
+                write!(f, "reclassified from {:?} to {:?}", from, to)?
+            }
+            ChangeKind::Reordered { from, to } => {
+                write!(f, "moved from position {} to {}", from, to)?
+            }
+        }
+        write!(f, " {:?}", self.vehicle.vehicle_type)?;
+        if let Some(number) = self.vehicle.order_number {
+            write!(f, " {}", number)?;
+        }
+        if let Some(evn) = self
+            .vehicle
+            .vehicle_identifier
+            .as_ref()
+            .and_then(|id| id.evn.as_ref())
+        {
+            write!(f, " ({})", evn)?;
+        }
+        Ok(())
+    }
+}
+
+/// What changed in the train between two consecutive stops. The train
+/// leaves `from` in the old formation and `to` in the new one, so every
+/// change happens at `to`.
+#[derive(Debug, Clone)]
+pub struct CompositionDiff {
+    pub from_stop: usize,
+    pub to_stop: usize,
+    pub from: StopPoint,
+    pub to: StopPoint,
+    pub changes: Vec<VehicleChange>,
+}
+
+impl CompositionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Coaches coupled at `to`, locomotives not counted.
+    pub fn coupled_coaches(&self) -> usize {
+        self.changes
+            .iter()
+            .filter(|c| c.kind == ChangeKind::Coupled && c.is_coach())
+            .count()
+    }
+
+    /// Coaches left behind at `to`, locomotives not counted.
+    pub fn uncoupled_coaches(&self) -> usize {
+        self.changes
+            .iter()
+            .filter(|c| c.kind == ChangeKind::Uncoupled && c.is_coach())
+            .count()
+    }
+}
+
+impl fmt::Display for CompositionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}:", self.from.name, self.to.name)?;
+        if self.changes.is_empty() {
+            return write!(f, " unchanged");
+        }
+        for (i, change) in self.changes.iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(f, "{}{}", sep, change)?;
+        }
+        Ok(())
+    }
+}
+
+impl FormationResponse {
+    /// Compare the vehicles at `stop_index` with those at the next stop.
+    /// `None` for the last stop.
+    ///
+    /// Vehicles are matched by EVN, then by order number; vehicles with
+    /// neither are matched by type. A train that only changed direction is
+    /// not reported as reordered.
+    pub fn composition_diff(&self, stop_index: usize) -> Option<CompositionDiff> {
+        let next = stop_index + 1;
+        if next >= self.formations_at_scheduled_stops.len() {
+            return None;
+        }
+        let before = running_vehicles(parse_formation_for_stop(self, stop_index));
+        let after = running_vehicles(parse_formation_for_stop(self, next));
+
+        let mut changes = Vec::new();
+
+        // Keyed vehicles, in train order.
+        let before_keys: Vec<(Key, &Vehicle)> = keyed(&before);
+        let after_keys: Vec<(Key, &Vehicle)> = keyed(&after);
+        let before_map: HashMap<&Key, &Vehicle> =
+            before_keys.iter().map(|(k, v)| (k, *v)).collect();
+        let after_map: HashMap<&Key, &Vehicle> = after_keys.iter().map(|(k, v)| (k, *v)).collect();
+
+        for (key, vehicle) in before_keys.iter() {
+            if !after_map.contains_key(key) {
+                changes.push(change(ChangeKind::Uncoupled, vehicle));
+            }
+        }
+        for (key, vehicle) in after_keys.iter() {
+            match before_map.get(key) {
+                None => changes.push(change(ChangeKind::Coupled, vehicle)),
+                Some(old) => changes.extend(compare(old, vehicle)),
+            }
+        }
+
+        // Unkeyed vehicles, e.g. unnumbered closed coaches or locomotives
+        // without EVN: only the count per type can change.
+        let mut unkeyed: Vec<(VehicleType, Vec<&Vehicle>, Vec<&Vehicle>)> = Vec::new();
+        for (side, vehicles) in [(0, &before), (1, &after)] {
+            for vehicle in vehicles.iter().filter(|v| key_of(v).is_none()) {
+                let index = match unkeyed
+                    .iter()
+                    .position(|(t, _, _)| *t == vehicle.vehicle_type)
+                {
+                    Some(index) => index,
+                    None => {
+                        unkeyed.push((vehicle.vehicle_type.clone(), Vec::new(), Vec::new()));
+                        unkeyed.len() - 1
+                    }
+                };
+                if side == 0 {
+                    unkeyed[index].1.push(vehicle);
+                } else {
+                    unkeyed[index].2.push(vehicle);
+                }
+            }
+        }
+        for (_, old, new) in unkeyed.iter() {
+            for vehicle in old.iter().skip(new.len()) {
+                changes.push(change(ChangeKind::Uncoupled, vehicle));
+            }
+            for vehicle in new.iter().skip(old.len()) {
+                changes.push(change(ChangeKind::Coupled, vehicle));
+            }
+        }
+
+        changes.extend(reordered(&before_keys, &after_keys));
+
+        Some(CompositionDiff {
+            from_stop: stop_index,
+            to_stop: next,
+            from: self.formations_at_scheduled_stops[stop_index]
+                .scheduled_stop
+                .stop_point
+                .clone(),
+            to: self.formations_at_scheduled_stops[next]
+                .scheduled_stop
+                .stop_point
+                .clone(),
+            changes,
+        })
+    }
+
+    /// Diffs for every pair of consecutive stops.
+    pub fn composition_diffs(&self) -> Vec<CompositionDiff> {
+        (0..self.formations_at_scheduled_stops.len())
+            .filter_map(|i| self.composition_diff(i))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Evn(String),
+    Number(u32),
+}
+
+fn key_of(vehicle: &Vehicle) -> Option<Key> {
+    if let Some(evn) = vehicle
+        .vehicle_identifier
+        .as_ref()
+        .and_then(|id| id.evn.clone())
+    {
+        return Some(Key::Evn(evn));
+    }
+    vehicle.order_number.map(Key::Number)
+}
+
+fn keyed(vehicles: &[Vehicle]) -> Vec<(Key, &Vehicle)> {
+    let mut keys: Vec<(Key, &Vehicle)> = Vec::new();
+    for vehicle in vehicles {
+        if let Some(key) = key_of(vehicle)
+            && !keys.iter().any(|(k, _)| *k == key)
+        {
+            keys.push((key, vehicle));
+        }
+    }
+    keys
+}
+
+fn running_vehicles(vehicles: Vec<Vehicle>) -> Vec<Vehicle> {
+    vehicles
+        .into_iter()
+        .filter(|v| !matches!(v.vehicle_type, VehicleType::Fictional | VehicleType::Parked))
+        .collect()
+}
+
+fn change(kind: ChangeKind, vehicle: &Vehicle) -> VehicleChange {
+    VehicleChange {
+        kind,
+        vehicle: vehicle.clone(),
+    }
+}
+
+fn compare(old: &Vehicle, new: &Vehicle) -> Vec<VehicleChange> {
+    let mut changes = Vec::new();
+    let was_closed = old.status.contains(&StatusFlag::Closed);
+    let is_closed = new.status.contains(&StatusFlag::Closed);
+    if !was_closed && is_closed {
+        changes.push(change(ChangeKind::Closed, new));
+    } else if was_closed && !is_closed {
+        changes.push(change(ChangeKind::Reopened, new));
+    }
+    if old.vehicle_type != new.vehicle_type {
+        changes.push(change(
+            ChangeKind::Reclassified {
+                from: old.vehicle_type.clone(),
+                to: new.vehicle_type.clone(),
+            },
+            new,
+        ));
+    }
+    changes
+}
+
+/// Vehicles that moved relative to the others. The ones outside the longest
+/// run that kept its order (in either direction) are reported.
+fn reordered(before: &[(Key, &Vehicle)], after: &[(Key, &Vehicle)]) -> Vec<VehicleChange> {
+    let common_before: Vec<&Key> = before
+        .iter()
+        .map(|(k, _)| k)
+        .filter(|k| after.iter().any(|(a, _)| a == *k))
+        .collect();
+    let common_after: Vec<(&Key, &Vehicle)> = after
+        .iter()
+        .filter(|(k, _)| common_before.contains(&k))
+        .map(|(k, v)| (k, *v))
+        .collect();
+
+    let positions: Vec<usize> = common_after
+        .iter()
+        .map(|(k, _)| common_before.iter().position(|b| b == k).unwrap())
+        .collect();
+    let reversed: Vec<usize> = positions.iter().rev().copied().collect();
+
+    let forward = longest_increasing(&positions);
+    let backward = longest_increasing(&reversed);
+    let (kept, flipped) = if backward.len() > forward.len() {
+        (backward, true)
+    } else {
+        (forward, false)
+    };
+
+    let len = common_after.len();
+    let mut changes = Vec::new();
+    for (index, (_, vehicle)) in common_after.iter().enumerate() {
+        let oriented = if flipped { len - 1 - index } else { index };
+        if !kept.contains(&oriented) {
+            changes.push(change(
+                ChangeKind::Reordered {
+                    from: positions[index] + 1,
+                    to: index + 1,
+                },
+                vehicle,
+            ));
+        }
+    }
+    changes
+}
+
+/// Indices of one longest strictly increasing subsequence.
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    let mut length = vec![1usize; values.len()];
+    let mut previous = vec![None; values.len()];
+    for i in 0..values.len() {
+        for j in 0..i {
+            if values[j] < values[i] && length[j] + 1 > length[i] {
+                length[i] = length[j] + 1;
+                previous[i] = Some(j);
+            }
+        }
+    }
+    let Some(mut current) = (0..values.len()).max_by_key(|&i| length[i]) else {
+        return Vec::new();
+    };
+    let mut indices = vec![current];
+    while let Some(p) = previous[current] {
+        indices.push(p);
+        current = p;
+    }
+    indices.reverse();
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_formation_json;
+
+    fn fixture() -> crate::FormationResponse {
+        parse_formation_json(include_str!("../test_data/test_response.json")).unwrap()
+    }
+
+    #[test]
+    fn coupled_at_the_later_stop() {
+        let mut train = fixture();
+        // Two coaches join at Spiez.
+        train.formations_at_scheduled_stops[2]
+            .formation_short
+            .formation_short_string
+            .push_str(",[2:21,2:22]");
+        assert_eq!(train.composition_diff(0).unwrap().coupled_coaches(), 0);
+        let diff = train.composition_diff(1).unwrap();
+        assert_eq!(diff.to.name, "Spiez");
+        assert_eq!(diff.coupled_coaches(), 2);
+        let back = train.composition_diff(2).unwrap();
+        assert_eq!(back.to.name, "Thun");
+        assert_eq!(back.uncoupled_coaches(), 2);
+    }
+
+    #[test]
+    fn turning_is_not_reordering() {
+        // The train reverses in Zürich HB.
+        let train = fixture();
+        let diff = train.composition_diff(4).unwrap();
+        assert_eq!(diff.to.name, "Zürich HB");
+        assert!(diff.is_empty());
+        assert!(train.composition_diff(5).unwrap().is_empty());
+        assert_eq!(train.composition_diffs().len(), 11);
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+
+use crate::short_string::{Node, Token, VehicleNode, parse_short_string};
+use crate::{FormationResponse, Offer, StopPoint, Vehicle, VehicleType, parse_formation_for_stop};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiagnosticKind {
+    UnknownVehicleType,
+    UnknownOffer,
+    /// Text after `:` that is not a number.
+    InvalidOrderNumber,
+    /// The string does not follow the formation grammar; vehicles come from
+    /// the lenient splitter instead.
+    Syntax(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub token: String,
+    /// Byte offset of `token` in `formation_short_string`.
+    pub offset: usize,
+    pub stop: StopPoint,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match &self.kind {
+            DiagnosticKind::UnknownVehicleType => "unknown vehicle type".to_string(),
+            DiagnosticKind::UnknownOffer => "unknown offer".to_string(),
+            DiagnosticKind::InvalidOrderNumber => "invalid order number".to_string(),
+            DiagnosticKind::Syntax(message) => message.clone(),
+        };
+        write!(
+            f,
+            "{} `{}` at byte {} ({}, {})",
+            what, self.token, self.offset, self.stop.name, self.stop.uic
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StrictParse {
+    pub vehicles: Vec<Vehicle>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Like [`parse_formation_for_stop`], but also reports every token the parser
+/// had to guess about.
+pub fn parse_formation_for_stop_strict(
+    train: &FormationResponse,
+    stop_index: usize,
+) -> StrictParse {
+    StrictParse {
+        vehicles: parse_formation_for_stop(train, stop_index),
+        diagnostics: diagnose_stop(train, stop_index),
+    }
+}
+
+/// Diagnostics for every stop of a train.
+pub fn diagnose_formation(train: &FormationResponse) -> Vec<Diagnostic> {
+    (0..train.formations_at_scheduled_stops.len())
+        .flat_map(|i| diagnose_stop(train, i))
+        .collect()
+}
+
+/// Diagnostics from the formation string alone, without building vehicles.
+fn diagnose_stop(train: &FormationResponse, stop_index: usize) -> Vec<Diagnostic> {
+    let stop = &train.formations_at_scheduled_stops[stop_index];
+    let input = &stop.formation_short.formation_short_string;
+    let stop_point = &stop.scheduled_stop.stop_point;
+
+    let mut diagnostics = Vec::new();
+    match parse_short_string(input) {
+        Ok(formation) => collect(&formation.nodes, stop_point, &mut diagnostics),
+        Err(err) => {
+            let token = input[err.offset..]
+                .split(',')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            diagnostics.push(Diagnostic {
+                kind: DiagnosticKind::Syntax(err.message),
+                token,
+                offset: err.offset,
+                stop: stop_point.clone(),
+            });
+        }
+    }
+    diagnostics
+}
+
+fn collect(nodes: &[Node], stop: &StopPoint, diagnostics: &mut Vec<Diagnostic>) {
+    for node in nodes {
+        match node {
+            Node::Vehicle(vehicle) => check_vehicle(vehicle, stop, diagnostics),
+            Node::Unit(unit) => collect(&unit.nodes, stop, diagnostics),
+            Node::Group(group) => collect(&group.nodes, stop, diagnostics),
+            Node::Sector(_) => {}
+        }
+    }
+}
+
+fn check_vehicle(vehicle: &VehicleNode, stop: &StopPoint, diagnostics: &mut Vec<Diagnostic>) {
+    let mut push = |kind, token: &Token| {
+        diagnostics.push(Diagnostic {
+            kind,
+            token: token.text.clone(),
+            offset: token.span.start,
+            stop: stop.clone(),
+        })
+    };
+
+    if let VehicleType::Unknown(_) = vehicle.vehicle_type() {
+        push(DiagnosticKind::UnknownVehicleType, &vehicle.type_code);
+    }
+    if let Some(number) = vehicle.number.as_ref()
+        && vehicle.order_number().is_none()
+    {
+        push(DiagnosticKind::InvalidOrderNumber, number);
+    }
+    for (offer, token) in vehicle.offers().iter().zip(vehicle.offers.iter()) {
+        if let Offer::Unknown(_) = offer {
+            push(DiagnosticKind::UnknownOffer, token);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_formation_json;
+
+    /// The fixture train with `strings` as the formations at its first
+    /// stops.
+    fn train(strings: &[&str]) -> FormationResponse {
+        let mut train =
+            parse_formation_json(include_str!("../test_data/test_response.json")).unwrap();
+        train.formations_at_scheduled_stops.truncate(strings.len());
+        for (stop, string) in train.formations_at_scheduled_stops.iter_mut().zip(strings) {
+            stop.formation_short.formation_short_string = string.to_string();
+        }
+        train
+    }
+
+    fn kinds_and_offsets(diagnostics: &[Diagnostic]) -> Vec<(DiagnosticKind, &str, usize)> {
+        diagnostics
+            .iter()
+            .map(|d| (d.kind.clone(), d.token.as_str(), d.offset))
+            .collect()
+    }
+
+    #[test]
+    fn fixture_is_clean() {
+        let train = parse_formation_json(include_str!("../test_data/test_response.json")).unwrap();
+        assert!(diagnose_formation(&train).is_empty());
+    }
+
+    #[test]
+    fn unknown_tokens() {
+        let strict = parse_formation_for_stop_strict(&train(&["@A,LK,QQ:1,2:2#NF;XY,2:x"]), 0);
+        assert_eq!(strict.vehicles.len(), 4);
+        assert_eq!(
+            kinds_and_offsets(&strict.diagnostics),
+            vec![
+                (DiagnosticKind::UnknownVehicleType, "QQ", 6),
+                (DiagnosticKind::UnknownOffer, "XY", 18),
+                (DiagnosticKind::InvalidOrderNumber, "x", 23),
+            ]
+        );
+        assert_eq!(
+            strict.diagnostics[0].to_string(),
+            "unknown vehicle type `QQ` at byte 6 (Brig, 8501609)"
+        );
+    }
+
+    #[test]
+    fn syntax_error() {
+        let strict = parse_formation_for_stop_strict(&train(&["@A,[LK,2:1"]), 0);
+        // The lenient splitter still finds the vehicles.
+        assert_eq!(strict.vehicles.len(), 2);
+        let [diagnostic] = strict.diagnostics.as_slice() else {
+            panic!("expected one diagnostic, got {:?}", strict.diagnostics);
+        };
+        assert!(matches!(diagnostic.kind, DiagnosticKind::Syntax(_)));
+        assert_eq!(diagnostic.offset, 10);
+    }
+
+    #[test]
+    fn every_stop_is_diagnosed() {
+        let diagnostics = diagnose_formation(&train(&["2:1,QQ:2", "2:1", "2:1#XY"]));
+        assert_eq!(
+            kinds_and_offsets(&diagnostics),
+            vec![
+                (DiagnosticKind::UnknownVehicleType, "QQ", 4),
+                (DiagnosticKind::UnknownOffer, "XY", 4),
+            ]
+        );
+        assert_eq!(diagnostics[0].stop.name, "Brig");
+        assert_eq!(diagnostics[1].stop.name, "Spiez");
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::{BTreeSet, HashSet};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc};
+
+use crate::{
+    LineCatalog, OtdError, StopEvent, StopEventRequest, StopEventType, parse_stop_event_results,
+};
+
+/// A station whose departures are searched for trains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HubStop {
+    pub uic: u32,
+    pub name: String,
+}
+
+impl HubStop {
+    pub fn new(uic: u32, name: impl Into<String>) -> Self {
+        HubStop {
+            uic,
+            name: name.into(),
+        }
+    }
+
+    pub fn bern() -> Self {
+        HubStop::new(8507000, "Bern")
+    }
+}
+
+/// Stop events found for a [`DiscoveryQuery`].
+#[derive(Debug, Default)]
+pub struct Discovered {
+    /// Active events of trains on a monitored line.
+    pub events: Vec<StopEvent>,
+    /// `StopEventResult`s that could not be read, for logging.
+    pub skipped: Vec<OtdError>,
+    /// Active events with a monitored train number whose operator neither
+    /// OJP nor the line catalog tells, for logging.
+    pub unidentified: Vec<StopEvent>,
+}
+
+impl Discovered {
+    /// Add the events and skipped results of another hub. A journey already
+    /// found at an earlier hub keeps its first event.
+    pub fn merge(&mut self, other: Discovered) {
+        let mut seen: HashSet<(NaiveDate, String)> = self
+            .events
+            .iter()
+            .chain(self.unidentified.iter())
+            .map(journey)
+            .collect();
+        self.events.extend(
+            other
+                .events
+                .into_iter()
+                .filter(|event| seen.insert(journey(event))),
+        );
+        self.unidentified.extend(
+            other
+                .unidentified
+                .into_iter()
+                .filter(|event| seen.insert(journey(event))),
+        );
+        self.skipped.extend(other.skipped);
+    }
+}
+
+/// Identifies the journey of `event` across hubs.
+fn journey(event: &StopEvent) -> (NaiveDate, String) {
+    (
+        event.service.operating_day,
+        event.service.journey_ref.clone(),
+    )
+}
+
+/// Which trains to look for: departures from one or more hubs within a time
+/// window of an operation date, kept if they belong to a line in `lines`.
+#[derive(Debug, Clone)]
+pub struct DiscoveryQuery {
+    pub operation_date: NaiveDate,
+    pub hubs: Vec<HubStop>,
+    /// Local (Europe/Zurich) time the window opens on `operation_date`.
+    pub from: NaiveTime,
+    pub window: TimeDelta,
+    pub lines: LineCatalog,
+    /// Drop trains whose last arrival is before this.
+    pub active_after: Option<DateTime<Utc>>,
+    pub results_per_hub: u32,
+}
+
+impl DiscoveryQuery {
+    /// Bern, from 04:00 for 24 hours, on the built-in lines.
+    pub fn new(operation_date: NaiveDate) -> Self {
+        DiscoveryQuery {
+            operation_date,
+            hubs: vec![HubStop::bern()],
+            from: NaiveTime::from_hms_opt(4, 0, 0).unwrap(),
+            window: TimeDelta::hours(24),
+            lines: LineCatalog::builtin().clone(),
+            active_after: None,
+            results_per_hub: 1000,
+        }
+    }
+
+    /// Today's service day in Zurich, without trains that already arrived.
+    pub fn today() -> Self {
+        let now = Utc::now();
+        DiscoveryQuery::new(now.with_timezone(&chrono_tz::Europe::Zurich).date_naive())
+            .active_after(now)
+    }
+
+    /// Replace the hubs.
+    pub fn hubs(mut self, hubs: impl IntoIterator<Item = HubStop>) -> Self {
+        self.hubs = hubs.into_iter().collect();
+        self
+    }
+
+    pub fn add_hub(mut self, uic: u32, name: impl Into<String>) -> Self {
+        self.hubs.push(HubStop::new(uic, name));
+        self
+    }
+
+    pub fn window(mut self, from: NaiveTime, length: TimeDelta) -> Self {
+        self.from = from;
+        self.window = length;
+        self
+    }
+
+    pub fn lines(mut self, lines: LineCatalog) -> Self {
+        self.lines = lines;
+        self
+    }
+
+    pub fn active_after(mut self, at: DateTime<Utc>) -> Self {
+        self.active_after = Some(at);
+        self
+    }
+
+    pub fn results_per_hub(mut self, results: u32) -> Self {
+        self.results_per_hub = results;
+        self
+    }
+
+    pub fn window_start(&self) -> DateTime<Utc> {
+        chrono_tz::Europe::Zurich
+            .from_local_datetime(&self.operation_date.and_time(self.from))
+            .earliest()
+            .map(|start| start.with_timezone(&Utc))
+            .unwrap_or_else(|| self.operation_date.and_time(self.from).and_utc())
+    }
+
+    pub fn window_end(&self) -> DateTime<Utc> {
+        self.window_start() + self.window
+    }
+
+    /// Departures at `hub` from the start of the window.
+    pub fn stop_event_request(&self, hub: &HubStop) -> StopEventRequest {
+        StopEventRequest::new(hub.uic.to_string(), self.window_start())
+            .stop_name(hub.name.as_str())
+            .event_type(StopEventType::Departure)
+            .number_of_results(self.results_per_hub)
+    }
+
+    pub fn request_body(&self, hub: &HubStop) -> String {
+        self.stop_event_request(hub).to_xml()
+    }
+
+    /// Departs from the hub within the window and, with `active_after`, has
+    /// not arrived at its last stop yet (going by realtime where known).
+    pub fn is_active(&self, event: &StopEvent) -> bool {
+        let departs_in_window = event.this_call.departure.is_some_and(|departure| {
+            departure.timetabled >= self.window_start() && departure.timetabled <= self.window_end()
+        });
+        let still_running = match (event.last_arrival(), self.active_after) {
+            (Some(latest), Some(after)) => latest > after,
+            _ => true,
+        };
+        departs_in_window && still_running
+    }
+
+    /// Active stop events in one hub's response for trains on a monitored
+    /// line. Results that cannot be read are skipped and returned too, as are
+    /// trains whose operator is not known.
+    pub fn stop_events(&self, xml: &str) -> Result<Discovered, OtdError> {
+        let mut discovered = Discovered::default();
+        for result in parse_stop_event_results(xml)? {
+            let mut event = match result {
+                Ok(event) => event,
+                Err(e) => {
+                    discovered.skipped.push(e);
+                    continue;
+                }
+            };
+            let Some(train_number) = event.train_number() else {
+                continue;
+            };
+            let monitored = self
+                .lines
+                .lines
+                .iter()
+                .any(|line| line.contains_number(train_number));
+            if !monitored || !self.is_active(&event) {
+                continue;
+            }
+            if event.operator.is_none() {
+                let stops: Vec<&str> = event
+                    .calls()
+                    .map(|call| call.stop_point_name.as_str())
+                    .collect();
+                event.operator = self.lines.operator_for(train_number, &stops).cloned();
+            }
+            match event.train_id() {
+                Some(train) if self.lines.contains(&train) => discovered.events.push(event),
+                Some(_) => {}
+                None => discovered.unidentified.push(event),
+            }
+        }
+        Ok(discovered)
+    }
+
+    /// Train numbers in one hub's response that belong to a monitored line.
+    pub fn train_numbers(&self, xml: &str) -> Result<Vec<i32>, OtdError> {
+        let trains = self
+            .stop_events(xml)?
+            .events
+            .iter()
+            .filter_map(StopEvent::train_number)
+            .collect();
+        Ok(merge_train_numbers([trains]))
+    }
+}
+
+/// Merge the results of several hubs, sorted and without duplicates.
+pub(crate) fn merge_train_numbers(results: impl IntoIterator<Item = Vec<i32>>) -> Vec<i32> {
+    results
+        .into_iter()
+        .flatten()
+        .collect::<BTreeSet<i32>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = include_str!("../test_data/stop_event_response.xml");
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 1, 31).unwrap()
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn numbers(events: &[StopEvent]) -> Vec<i32> {
+        events.iter().filter_map(StopEvent::train_number).collect()
+    }
+
+    #[test]
+    fn monitored_lines_only() {
+        // 2519 is on no line of the built-in catalog.
+        let discovered = DiscoveryQuery::new(date()).stop_events(RESPONSE).unwrap();
+        assert_eq!(numbers(&discovered.events), vec![825, 965]);
+        assert!(discovered.skipped.is_empty());
+        assert!(discovered.unidentified.is_empty());
+
+        let lines = LineCatalog::from_toml(
+            r#"
+            [[line]]
+            id = "IC6"
+            display_name = "IC 6"
+            numbers = [[950, 999]]
+            "#,
+        )
+        .unwrap();
+        let query = DiscoveryQuery::new(date()).lines(lines);
+        assert_eq!(
+            numbers(&query.stop_events(RESPONSE).unwrap().events),
+            vec![965]
+        );
+        assert_eq!(query.train_numbers(RESPONSE).unwrap(), vec![965]);
+    }
+
+    #[test]
+    fn journeys_from_several_hubs_are_merged() {
+        let query = DiscoveryQuery::new(date()).add_hub(8503000, "Zürich HB");
+        let mut discovered = query.stop_events(RESPONSE).unwrap();
+        // The same journeys, also departing from Zürich HB.
+        let zurich = RESPONSE.replace("ID-6A0F8B7C", "ID-ZH");
+        discovered.merge(query.stop_events(&zurich).unwrap());
+        assert_eq!(numbers(&discovered.events), vec![825, 965]);
+        assert_eq!(discovered.events[0].id, "ID-6A0F8B7C-1");
+
+        // Another operating day is another journey.
+        let tomorrow = RESPONSE.replace(
+            "<OperatingDayRef>2026-01-31</OperatingDayRef>",
+            "<OperatingDayRef>2026-02-01</OperatingDayRef>",
+        );
+        discovered.merge(query.stop_events(&tomorrow).unwrap());
+        assert_eq!(numbers(&discovered.events), vec![825, 965, 825, 965]);
+    }
+
+    #[test]
+    fn unidentified_journeys_are_merged() {
+        let unknown = RESPONSE.replace(
+            "<siri:OperatorRef>11</siri:OperatorRef>",
+            "<siri:OperatorRef>999</siri:OperatorRef>",
+        );
+        let query = DiscoveryQuery::new(date()).lines(
+            LineCatalog::from_toml(
+                r#"
+            [[line]]
+            id = "IC8"
+            display_name = "IC 8"
+            numbers = [[800, 899]]
+
+            [[line]]
+            id = "RE"
+            display_name = "RE"
+            numbers = [[800, 899]]
+            operator = "BLSP"
+            "#,
+            )
+            .unwrap(),
+        );
+        let mut discovered = query.stop_events(&unknown).unwrap();
+        discovered.merge(query.stop_events(&unknown).unwrap());
+        assert!(discovered.events.is_empty());
+        assert_eq!(numbers(&discovered.unidentified), vec![825]);
+    }
+
+    #[test]
+    fn window_across_dst() {
+        // Clocks go forward on 2026-03-29 and back on 2026-10-25.
+        let spring = DiscoveryQuery::new(NaiveDate::from_ymd_opt(2026, 3, 29).unwrap());
+        assert_eq!(spring.window_start(), at("2026-03-29T02:00:00Z"));
+        assert_eq!(spring.window_end(), at("2026-03-30T02:00:00Z"));
+        // 02:30 does not exist that night.
+        let gap = spring.clone().window(
+            NaiveTime::from_hms_opt(2, 30, 0).unwrap(),
+            TimeDelta::hours(1),
+        );
+        assert_eq!(gap.window_start(), at("2026-03-29T02:30:00Z"));
+
+        let autumn = DiscoveryQuery::new(NaiveDate::from_ymd_opt(2026, 10, 25).unwrap());
+        assert_eq!(autumn.window_start(), at("2026-10-25T03:00:00Z"));
+        assert_eq!(autumn.window_end(), at("2026-10-26T03:00:00Z"));
+        // 02:30 happens twice; the window opens at the first.
+        let twice = autumn.window(
+            NaiveTime::from_hms_opt(2, 30, 0).unwrap(),
+            TimeDelta::hours(1),
+        );
+        assert_eq!(twice.window_start(), at("2026-10-25T00:30:00Z"));
+    }
+
+    #[test]
+    fn active_in_window_and_running() {
+        let events = parse_stop_event_results(RESPONSE)
+            .unwrap()
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        // 825 leaves Bern at 15:04Z and arrives at 17:01Z, 965 leaves at
+        // 15:02Z and arrives at 15:57Z.
+        let query = DiscoveryQuery::new(date());
+        assert!(query.is_active(&events[0]) && query.is_active(&events[1]));
+
+        let query = query.active_after(at("2026-01-31T16:00:00Z"));
+        assert!(query.is_active(&events[0]));
+        assert!(!query.is_active(&events[1]));
+
+        let query = DiscoveryQuery::new(date()).window(
+            NaiveTime::from_hms_opt(16, 3, 0).unwrap(),
+            TimeDelta::hours(1),
+        );
+        // The window opens at 15:03Z, just after 965 left.
+        assert!(query.is_active(&events[0]));
+        assert!(!query.is_active(&events[1]));
+        assert!(
+            !DiscoveryQuery::new(NaiveDate::from_ymd_opt(2026, 2, 1).unwrap())
+                .is_active(&events[0])
+        );
+    }
+}
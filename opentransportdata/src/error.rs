@@ -0,0 +1,189 @@
+use std::fmt;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+/// Error returned by every opentransportdata endpoint (formation API and OJP).
+#[derive(Debug)]
+pub enum OtdError {
+    /// The server answered with a non-success status code.
+    Http {
+        status: u16,
+        /// Parsed `Retry-After` header, if the server sent one.
+        retry_after: Option<Duration>,
+    },
+    /// The request never produced a response (DNS, TLS, timeout, ...).
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+    /// The response body is not the JSON we expect.
+    Json {
+        /// Path to the failing field, e.g. `formationsAtScheduledStops[3].stopTime`.
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// The OJP response is not the XML we expect.
+    Xml {
+        /// Path to the failing element, e.g.
+        /// `StopEventResult[2].StopEvent.Service.OperatingDayRef`.
+        path: String,
+        /// Byte offset where reading stopped, in the response with its
+        /// namespace prefixes removed.
+        position: u64,
+        message: String,
+    },
+    /// A configuration file (e.g. detection rules) is invalid.
+    Config(String),
+}
+
+impl OtdError {
+    /// HTTP status code, if the server answered at all.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            OtdError::Http { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            OtdError::Http { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// `429 Too Many Requests`
+    pub fn is_rate_limited(&self) -> bool {
+        self.status() == Some(429)
+    }
+
+    pub(crate) fn from_json(err: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        let path = err.path().to_string();
+        let inner = err.into_inner();
+        OtdError::Json {
+            path,
+            line: inner.line(),
+            column: inner.column(),
+            message: inner.to_string(),
+        }
+    }
+
+    pub(crate) fn from_xml(
+        err: serde_path_to_error::Error<quick_xml::DeError>,
+        position: u64,
+    ) -> Self {
+        OtdError::Xml {
+            path: err.path().to_string(),
+            position,
+            message: err.into_inner().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for OtdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtdError::Http {
+                status,
+                retry_after: Some(retry_after),
+            } => write!(
+                f,
+                "API request failed with status {} (retry after {}s)",
+                status,
+                retry_after.as_secs()
+            ),
+            OtdError::Http { status, .. } => {
+                write!(f, "API request failed with status {}", status)
+            }
+            OtdError::Transport(e) => write!(f, "HTTP request failed: {}", e),
+            OtdError::Json {
+                path,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "JSON parsing error at `{}` (line {}, column {}): {}",
+                path, line, column, message
+            ),
+            OtdError::Xml {
+                path,
+                position,
+                message,
+            } => write!(
+                f,
+                "XML parsing error at `{}` (byte {}): {}",
+                path, position, message
+            ),
+            OtdError::Config(message) => write!(f, "Invalid configuration: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for OtdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OtdError::Transport(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "native-client")]
+impl From<reqwest::Error> for OtdError {
+    fn from(err: reqwest::Error) -> Self {
+        OtdError::Transport(Box::new(err))
+    }
+}
+
+/// Parse a `Retry-After` header value, either delay-seconds or an HTTP date.
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        "2026-01-31T12:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        assert_eq!(
+            parse_retry_after("120", now()),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(parse_retry_after(" 0 ", now()), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_http_date() {
+        assert_eq!(
+            parse_retry_after("Sat, 31 Jan 2026 12:01:30 GMT", now()),
+            Some(Duration::from_secs(90))
+        );
+        // A date in the past means retry now.
+        assert_eq!(
+            parse_retry_after("Sat, 31 Jan 2026 11:00:00 GMT", now()),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn retry_after_garbage() {
+        for value in ["", "soon", "-5", "1.5", "2026-01-31T12:01:30Z"] {
+            assert_eq!(parse_retry_after(value, now()), None, "{value:?}");
+        }
+    }
+}
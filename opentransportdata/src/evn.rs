@@ -0,0 +1,218 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// A UIC European Vehicle Number, e.g. `50 85 19-94 012-8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Evn {
+    /// Digits 1-2, e.g. `50` for a coach in domestic traffic, `93` for a
+    /// fixed-formation unit.
+    pub interoperability: u8,
+    /// Digits 3-4, the UIC country code (`85` for Switzerland).
+    pub country: u8,
+    /// Digits 5-8.
+    pub type_code: u16,
+    /// Digits 9-11.
+    pub serial: u16,
+    /// Digit 12, derived from the first eleven.
+    pub check_digit: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvnError {
+    /// Not 11 or 12 digits once spaces and dashes are removed.
+    Length(usize),
+    InvalidCharacter(char),
+    CheckDigit {
+        expected: u8,
+        found: u8,
+    },
+}
+
+impl fmt::Display for EvnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvnError::Length(len) => write!(f, "expected 11 or 12 digits, got {}", len),
+            EvnError::InvalidCharacter(c) => write!(f, "invalid character `{}`", c),
+            EvnError::CheckDigit { expected, found } => {
+                write!(f, "check digit is {}, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvnError {}
+
+impl Evn {
+    /// Build an EVN and compute its check digit.
+    pub fn new(interoperability: u8, country: u8, type_code: u16, serial: u16) -> Self {
+        let mut evn = Evn {
+            interoperability: interoperability % 100,
+            country: country % 100,
+            type_code: type_code % 10_000,
+            serial: serial % 1_000,
+            check_digit: 0,
+        };
+        evn.check_digit = check_digit(&evn.digits()[..11]);
+        evn
+    }
+
+    /// Parse an EVN, ignoring spaces and dashes. Without a 12th digit the
+    /// check digit is computed; with one it must match.
+    pub fn parse(input: &str) -> Result<Self, EvnError> {
+        let mut digits = Vec::with_capacity(12);
+        for c in input.chars() {
+            match c {
+                ' ' | '-' => {}
+                '0'..='9' => digits.push(c as u8 - b'0'),
+                _ => return Err(EvnError::InvalidCharacter(c)),
+            }
+        }
+        if digits.len() != 11 && digits.len() != 12 {
+            return Err(EvnError::Length(digits.len()));
+        }
+
+        let number = |range: std::ops::Range<usize>| {
+            digits[range]
+                .iter()
+                .fold(0u16, |acc, d| acc * 10 + *d as u16)
+        };
+        let evn = Evn::new(
+            number(0..2) as u8,
+            number(2..4) as u8,
+            number(4..8),
+            number(8..11),
+        );
+        if let Some(&found) = digits.get(11)
+            && found != evn.check_digit
+        {
+            return Err(EvnError::CheckDigit {
+                expected: evn.check_digit,
+                found,
+            });
+        }
+        Ok(evn)
+    }
+
+    /// The twelve digits, check digit last.
+    pub fn digits(&self) -> [u8; 12] {
+        let text = format!(
+            "{:02}{:02}{:04}{:03}{}",
+            self.interoperability, self.country, self.type_code, self.serial, self.check_digit
+        );
+        let mut digits = [0u8; 12];
+        for (digit, c) in digits.iter_mut().zip(text.bytes()) {
+            *digit = c - b'0';
+        }
+        digits
+    }
+
+    /// Traction units and multiple units start with a 9.
+    pub fn is_traction(&self) -> bool {
+        self.interoperability / 10 == 9
+    }
+}
+
+/// Luhn check digit as used by the UIC: every other digit doubled, starting
+/// with the first.
+fn check_digit(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            let value = if i % 2 == 0 { *d as u32 * 2 } else { *d as u32 };
+            value / 10 + value % 10
+        })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+impl fmt::Display for Evn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02} {:02} {:02}-{:02} {:03}-{}",
+            self.interoperability,
+            self.country,
+            self.type_code / 100,
+            self.type_code % 100,
+            self.serial,
+            self.check_digit
+        )
+    }
+}
+
+impl FromStr for Evn {
+    type Err = EvnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Evn::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_with_and_without_check_digit() {
+        let evn = Evn::parse("508519940128").unwrap();
+        assert_eq!(evn, Evn::new(50, 85, 1994, 12));
+        assert_eq!(evn.check_digit, 8);
+        assert_eq!(Evn::parse("50851994012"), Ok(evn));
+        assert_eq!("50 85 19-94 012-8".parse(), Ok(evn));
+        assert_eq!(evn.digits(), [5, 0, 8, 5, 1, 9, 9, 4, 0, 1, 2, 8]);
+    }
+
+    #[test]
+    fn wrong_check_digit() {
+        assert_eq!(
+            Evn::parse("50 85 19-94 012-3"),
+            Err(EvnError::CheckDigit {
+                expected: 8,
+                found: 3
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert_eq!(
+            Evn::parse("50.85.19-94 012-8"),
+            Err(EvnError::InvalidCharacter('.'))
+        );
+        assert_eq!(
+            Evn::parse("CH-SBB 50 85"),
+            Err(EvnError::InvalidCharacter('C'))
+        );
+        assert_eq!(Evn::parse("50 85 19-94"), Err(EvnError::Length(8)));
+        assert_eq!(Evn::parse("50 85 19-94 012-81"), Err(EvnError::Length(13)));
+        assert_eq!(Evn::parse(" - "), Err(EvnError::Length(0)));
+        assert_eq!(
+            EvnError::Length(8).to_string(),
+            "expected 11 or 12 digits, got 8"
+        );
+    }
+
+    #[test]
+    fn display() {
+        let evn = Evn::parse("508519940128").unwrap();
+        assert_eq!(evn.to_string(), "50 85 19-94 012-8");
+        let unit = Evn::new(93, 85, 502, 7);
+        assert_eq!(unit.to_string(), "93 85 05-02 007-4");
+        assert_eq!(unit.to_string().parse(), Ok(unit));
+    }
+
+    #[test]
+    fn accessors() {
+        let coach = Evn::parse("50 85 19-94 012-8").unwrap();
+        assert_eq!(coach.interoperability, 50);
+        assert_eq!(coach.country, 85);
+        assert_eq!(coach.type_code, 1994);
+        assert_eq!(coach.serial, 12);
+        assert!(!coach.is_traction());
+
+        assert!(Evn::new(93, 85, 502, 7).is_traction());
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate, TimeDelta, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::TrainId;
+
+/// Observations this many days old count half.
+const HALF_LIFE_DAYS: f64 = 28.0;
+/// Weight of the fleet-wide rate, in observations, so a train seen only a
+/// few times is not forecast at 0 or 100%.
+const PRIOR_WEIGHT: f64 = 2.0;
+
+/// Whether a train ran with a deklassiert coach on one operation day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    pub train: TrainId,
+    pub operation_date: NaiveDate,
+    pub deklassiert: bool,
+    /// Catalog line id, if known.
+    pub line: Option<String>,
+}
+
+/// How likely a train is to run with a deklassiert coach on a date.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Forecast {
+    pub train: TrainId,
+    pub operation_date: NaiveDate,
+    /// Between 0 and 1.
+    pub probability: f64,
+    /// Past days of the same kind (weekday or holiday) the forecast rests on.
+    pub similar_days: usize,
+    pub line: Option<String>,
+}
+
+/// Days with similar traffic: Monday to Thursday are alike, holidays run
+/// like weekends, and Fridays are a kind of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DayKind {
+    Weekday(Weekday),
+    Holiday,
+}
+
+impl DayKind {
+    fn of(date: NaiveDate) -> Self {
+        if is_holiday(date) {
+            DayKind::Holiday
+        } else {
+            DayKind::Weekday(date.weekday())
+        }
+    }
+
+    fn is_leisure(self) -> bool {
+        matches!(
+            self,
+            DayKind::Holiday | DayKind::Weekday(Weekday::Sat | Weekday::Sun)
+        )
+    }
+
+    fn is_friday(self) -> bool {
+        self == DayKind::Weekday(Weekday::Fri)
+    }
+
+    fn similarity(self, other: DayKind) -> f64 {
+        if self == other {
+            1.0
+        } else if self.is_friday() || other.is_friday() {
+            0.1
+        } else if self.is_leisure() == other.is_leisure() {
+            0.5
+        } else {
+            0.02
+        }
+    }
+}
+
+/// Forecasts from past observations.
+///
+/// Each observation before the forecast date is weighted by how similar its
+/// day is (same weekday, both working days, both weekend or holiday, ...) and by its age, so
+/// recent weeks dominate. The weighted rate is pulled towards the rate of
+/// all trains on similar days.
+#[derive(Debug, Clone, Default)]
+pub struct Forecaster {
+    by_train: BTreeMap<TrainId, Vec<Observation>>,
+}
+
+impl Forecaster {
+    pub fn new(observations: impl IntoIterator<Item = Observation>) -> Self {
+        let mut by_train: BTreeMap<TrainId, Vec<Observation>> = BTreeMap::new();
+        for observation in observations {
+            by_train
+                .entry(observation.train.clone())
+                .or_default()
+                .push(observation);
+        }
+        Forecaster { by_train }
+    }
+
+    /// Trains with at least one observation.
+    pub fn trains(&self) -> impl Iterator<Item = &TrainId> {
+        self.by_train.keys()
+    }
+
+    /// `None` if `train` has no observation before `date`.
+    pub fn forecast(&self, train: &TrainId, date: NaiveDate) -> Option<Forecast> {
+        let kind = DayKind::of(date);
+        self.forecast_with_prior(train, date, kind, self.fleet_rate(date, kind))
+    }
+
+    /// Forecasts of at least `threshold` for `date`, most likely first.
+    pub fn likely(&self, date: NaiveDate, threshold: f64) -> Vec<Forecast> {
+        let kind = DayKind::of(date);
+        let prior = self.fleet_rate(date, kind);
+        let mut forecasts: Vec<Forecast> = self
+            .trains()
+            .filter_map(|train| self.forecast_with_prior(train, date, kind, prior))
+            .filter(|forecast| forecast.probability >= threshold)
+            .collect();
+        forecasts.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+        forecasts
+    }
+
+    /// `forecast` with the fleet rate for `date` already known.
+    fn forecast_with_prior(
+        &self,
+        train: &TrainId,
+        date: NaiveDate,
+        kind: DayKind,
+        prior: f64,
+    ) -> Option<Forecast> {
+        let observations = self.by_train.get(train)?;
+        let (mut deklassiert, mut total, mut similar_days) = (0.0, 0.0, 0);
+        for observation in observations
+            .iter()
+            .filter(|observation| observation.operation_date < date)
+        {
+            let weight = weight(observation.operation_date, date, kind);
+            total += weight;
+            if observation.deklassiert {
+                deklassiert += weight;
+            }
+            if DayKind::of(observation.operation_date) == kind {
+                similar_days += 1;
+            }
+        }
+        if total == 0.0 {
+            return None;
+        }
+        Some(Forecast {
+            train: train.clone(),
+            operation_date: date,
+            probability: (deklassiert + PRIOR_WEIGHT * prior) / (total + PRIOR_WEIGHT),
+            similar_days,
+            line: observations
+                .iter()
+                .rev()
+                .find_map(|observation| observation.line.clone()),
+        })
+    }
+
+    /// Weighted rate of all trains before `date`.
+    fn fleet_rate(&self, date: NaiveDate, kind: DayKind) -> f64 {
+        let (mut deklassiert, mut total) = (0.0, 0.0);
+        for observation in self
+            .by_train
+            .values()
+            .flatten()
+            .filter(|observation| observation.operation_date < date)
+        {
+            let weight = weight(observation.operation_date, date, kind);
+            total += weight;
+            if observation.deklassiert {
+                deklassiert += weight;
+            }
+        }
+        if total == 0.0 {
+            0.0
+        } else {
+            deklassiert / total
+        }
+    }
+}
+
+fn weight(observed: NaiveDate, date: NaiveDate, kind: DayKind) -> f64 {
+    let age = (date - observed).num_days() as f64;
+    0.5f64.powf(age / HALF_LIFE_DAYS) * DayKind::of(observed).similarity(kind)
+}
+
+/// Swiss public holidays observed in most cantons: New Year, Berchtoldstag,
+/// Good Friday, Easter Monday, Ascension, Whit Monday, National Day and
+/// Christmas.
+pub fn is_holiday(date: NaiveDate) -> bool {
+    if matches!(
+        (date.month(), date.day()),
+        (1, 1) | (1, 2) | (8, 1) | (12, 25) | (12, 26)
+    ) {
+        return true;
+    }
+    let Some(easter) = easter_sunday(date.year()) else {
+        return false;
+    };
+    [-2, 1, 39, 50]
+        .into_iter()
+        .any(|offset| easter + TimeDelta::days(offset) == date)
+}
+
+/// Gregorian Easter Sunday (anonymous Gregorian algorithm).
+fn easter_sunday(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Operator;
+
+    fn observations() -> Vec<Observation> {
+        let start = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        (0..28)
+            .flat_map(|day| {
+                let operation_date = start + TimeDelta::days(day);
+                [(812, day % 7 < 4), (965, day % 2 == 0), (1065, false)]
+                    .into_iter()
+                    .map(move |(number, deklassiert)| Observation {
+                        train: TrainId::new(Operator::Sbb, number),
+                        operation_date,
+                        deklassiert,
+                        line: None,
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn likely_matches_forecast() {
+        let forecaster = Forecaster::new(observations());
+        // A Monday; 812 ran with a deklassiert coach Monday to Thursday.
+        let date = NaiveDate::from_ymd_opt(2026, 3, 30).unwrap();
+        let likely = forecaster.likely(date, 0.0);
+        assert_eq!(likely.len(), 3);
+        for forecast in likely.iter() {
+            assert_eq!(
+                Some(forecast),
+                forecaster.forecast(&forecast.train, date).as_ref()
+            );
+        }
+        assert_eq!(likely[0].train.train_number, 812);
+        assert_eq!(likely[0].similar_days, 4);
+        assert!(
+            forecaster
+                .likely(date, 0.5)
+                .iter()
+                .all(|forecast| forecast.train.train_number != 1065)
+        );
+    }
+
+    #[test]
+    fn nothing_before_the_first_observation() {
+        let forecaster = Forecaster::new(observations());
+        let date = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        assert!(forecaster.likely(date, 0.0).is_empty());
+    }
+
+    #[test]
+    fn holidays() {
+        assert!(is_holiday(NaiveDate::from_ymd_opt(2026, 4, 3).unwrap()));
+        assert!(is_holiday(NaiveDate::from_ymd_opt(2026, 5, 14).unwrap()));
+        assert!(!is_holiday(NaiveDate::from_ymd_opt(2026, 4, 5).unwrap()));
+    }
+}
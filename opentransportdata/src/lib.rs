@@ -1,13 +1,64 @@
-use chrono::{DateTime, Datelike, FixedOffset, TimeZone};
+use chrono::{DateTime, FixedOffset, TimeDelta};
 use serde::de::Deserializer;
 use serde::{Deserialize, Serialize};
 
-use quick_xml::events::Event;
-// rust
-use quick_xml::Reader;
 use regex::Regex;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 
+mod archive;
+mod catalog;
+mod client;
+mod composition;
+mod diagnostics;
+mod discovery;
+mod error;
+mod evn;
+mod forecast;
+mod lines;
+mod ojp_request;
+mod operator;
+mod rate_limit;
+mod request_stats;
+mod rules;
+mod schedule;
+pub mod short_string;
+mod stations;
+mod stop_event;
+mod transport;
+mod trip_info;
+mod update;
+pub use archive::{Sighting, Statistics, sightings};
+pub use catalog::{CabEnd, Family, RollingStock, RollingStockCatalog, StockMatch, unit_end};
+pub use client::{ClientConfig, FORMATION_BASE_URL, FormationClient, OJP_URL, OjpClient};
+pub use composition::{ChangeKind, CompositionDiff, VehicleChange};
+pub use diagnostics::{
+    Diagnostic, DiagnosticKind, StrictParse, diagnose_formation, parse_formation_for_stop_strict,
+};
+pub use discovery::{Discovered, DiscoveryQuery, HubStop};
+pub use error::{OtdError, parse_retry_after};
+pub use evn::{Evn, EvnError};
+pub use forecast::{Forecast, Forecaster, Observation, is_holiday};
+pub use lines::{Line, LineCatalog};
+pub use ojp_request::{
+    LocationInformationRequest, LocationInput, RealtimeData, RequestContext, StopEventRequest,
+    StopEventType, TripInfoRequest,
+};
+pub use operator::{Operator, TrainId};
+pub use rate_limit::{Budget, LimiterStats, RateLimiter};
+pub use request_stats::{RequestCounts, RequestStats};
+pub use rules::{Action, Condition, Confidence, Rule, RuleSet, Verdict};
+pub use schedule::{RefreshScheduler, refresh_interval};
+pub use short_string::{FormationString, parse_short_string};
+pub use stations::{GeoPosition, Station, StationCache, parse_stations};
+pub use stop_event::{
+    CallAtStop, Service, ServiceTime, StopEvent, parse_stop_event_results, parse_stop_events,
+};
+#[cfg(feature = "native-client")]
+pub use transport::ReqwestTransport;
+pub use transport::{HttpRequest, HttpResponse, MemoryTransport, Method, Transport};
+pub use trip_info::{Discrepancy, DiscrepancyKind, JoinedStop, TripInfo, parse_trip_info};
+pub use update::{FormationUpdate, UpdateTracker, Validators};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum StatusFlag {
     Closed,        // -
@@ -57,6 +108,12 @@ pub struct Vehicle {
     pub order_number: Option<u32>,
     pub offers: Vec<Offer>,
     pub vehicle_identifier: Option<VehicleIdentifier>,
+    /// Index of the `[...]` traction unit the vehicle is coupled into.
+    pub traction_unit: Option<usize>,
+    /// Where the vehicle runs to, from the stop's `vehicleGoals`.
+    pub destination: Option<StopPoint>,
+    /// Why the vehicle carries [`StatusFlag::Deklassiert`].
+    pub deklassiert: Option<Verdict>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -70,6 +127,19 @@ pub struct FormationResponse {
     pub formations_at_scheduled_stops: Vec<FormationAtScheduledStop>,
     #[serde(default)]
     pub formations: Vec<Formation>,
+    /// The `evu` the formation was requested for; not part of the API
+    /// response.
+    #[serde(default)]
+    pub operator: Operator,
+}
+
+impl FormationResponse {
+    pub fn train_id(&self) -> TrainId {
+        TrainId::new(
+            self.operator.clone(),
+            self.train_meta_information.train_number as i32,
+        )
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -124,6 +194,31 @@ pub struct StopPoint {
 pub struct StopTime {
     pub arrival_time: Option<DateTime<FixedOffset>>,
     pub departure_time: Option<DateTime<FixedOffset>>,
+    /// Realtime arrival from OJP, see [`FormationResponse::apply_realtime`].
+    #[serde(default)]
+    pub estimated_arrival_time: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub estimated_departure_time: Option<DateTime<FixedOffset>>,
+}
+
+impl StopTime {
+    /// Estimated arrival if known, else the planned one.
+    pub fn expected_arrival(&self) -> Option<DateTime<FixedOffset>> {
+        self.estimated_arrival_time.or(self.arrival_time)
+    }
+
+    pub fn expected_departure(&self) -> Option<DateTime<FixedOffset>> {
+        self.estimated_departure_time.or(self.departure_time)
+    }
+
+    /// `None` without realtime data; negative when early.
+    pub fn arrival_delay(&self) -> Option<TimeDelta> {
+        Some(self.estimated_arrival_time? - self.arrival_time?)
+    }
+
+    pub fn departure_delay(&self) -> Option<TimeDelta> {
+        Some(self.estimated_departure_time? - self.departure_time?)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -216,6 +311,17 @@ pub struct VehicleIdentifier {
     pub position: Option<u32>,
 }
 
+impl VehicleIdentifier {
+    /// `evn` as a typed [`Evn`]; `None` if the API sent none.
+    pub fn parsed_evn(&self) -> Option<Result<Evn, EvnError>> {
+        self.evn.as_deref().map(Evn::parse)
+    }
+
+    pub fn parsed_parent_evn(&self) -> Option<Result<Evn, EvnError>> {
+        self.parent_evn.as_deref().map(Evn::parse)
+    }
+}
+
 fn null_to_empty<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
     D: Deserializer<'de>,
@@ -230,8 +336,9 @@ where
     Ok(Option::<String>::deserialize(deserializer)?.filter(|s| !s.is_empty()))
 }
 
-pub fn parse_formation_json(json: &str) -> Result<FormationResponse, serde_json::Error> {
-    serde_json::from_str::<FormationResponse>(json)
+pub fn parse_formation_json(json: &str) -> Result<FormationResponse, OtdError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(json);
+    serde_path_to_error::deserialize(deserializer).map_err(OtdError::from_json)
 }
 
 pub fn get_vehicle_information(
@@ -259,63 +366,25 @@ pub fn get_vehicle_information(
     map
 }
 
-fn deklassiert_by_number(
+fn trolley_status_by_number(
     train: &FormationResponse,
-) -> HashMap<u32, (bool, Option<VehicleIdentifier>)> {
+) -> HashMap<u32, (Option<TrolleyStatus>, Option<VehicleIdentifier>)> {
     let mut map = HashMap::new();
     if let Some(formation) = train.formations.first() {
         for vehicle in formation.formation_vehicles.iter() {
-            let is_deklassiert = matches!(
-                vehicle
-                    .vehicle_properties
-                    .as_ref()
-                    .and_then(|props| props.trolley_status.clone()),
-                Some(TrolleyStatus::Deklassiert)
-                    | Some(TrolleyStatus::RestaurantUnbedientDeklassiert)
-            );
+            let trolley_status = vehicle
+                .vehicle_properties
+                .as_ref()
+                .and_then(|props| props.trolley_status.clone());
             map.insert(
                 vehicle.number,
-                (is_deklassiert, vehicle.vehicle_identifier.clone()),
+                (trolley_status, vehicle.vehicle_identifier.clone()),
             );
         }
     }
     map
 }
 
-fn identifiers_by_position(train: &FormationResponse) -> Vec<Option<VehicleIdentifier>> {
-    let mut map: BTreeMap<u32, Option<VehicleIdentifier>> = BTreeMap::new();
-    for formation in train.formations.iter() {
-        for vehicle in formation.formation_vehicles.iter() {
-            map.entry(vehicle.position)
-                .or_insert(vehicle.vehicle_identifier.clone());
-        }
-    }
-    let max_pos = map.keys().copied().max().unwrap_or(0);
-    let mut identifiers = vec![None; max_pos as usize];
-    for (pos, identifier) in map {
-        if pos == 0 {
-            continue;
-        }
-        let index = pos.saturating_sub(1) as usize;
-        if let Some(slot) = identifiers.get_mut(index) {
-            *slot = identifier;
-        }
-    }
-    identifiers
-}
-
-fn positions_by_number(train: &FormationResponse) -> HashMap<u32, u32> {
-    let mut map = HashMap::new();
-    for formation in train.formations.iter() {
-        for vehicle in formation.formation_vehicles.iter() {
-            if vehicle.number > 0 {
-                map.insert(vehicle.number, vehicle.position);
-            }
-        }
-    }
-    map
-}
-
 fn sectors_by_position_for_stop(train: &FormationResponse, stop_uic: u32) -> Vec<Option<char>> {
     let mut map: BTreeMap<u32, Option<char>> = BTreeMap::new();
     for formation in train.formations.iter() {
@@ -407,6 +476,14 @@ fn parse_offer(s: &str) -> Offer {
 }
 
 fn parse_formation_short_string_raw(input: &str) -> Vec<Vehicle> {
+    match parse_short_string(input) {
+        Ok(formation) => formation.to_vehicles(),
+        Err(_) => split_formation_short_string(input),
+    }
+}
+
+/// Token splitting fallback for strings the grammar in [`short_string`] rejects.
+fn split_formation_short_string(input: &str) -> Vec<Vehicle> {
     let mut vehicles = Vec::new();
     let mut buf = String::new();
     let mut current_sector: Option<char> = None;
@@ -437,64 +514,40 @@ fn parse_formation_short_string_raw(input: &str) -> Vec<Vehicle> {
     vehicles
 }
 
+/// Parse the vehicles at `stop_index`, flagging deklassiert coaches with the
+/// built-in rules.
 pub fn parse_formation_for_stop(train: &FormationResponse, stop_index: usize) -> Vec<Vehicle> {
+    parse_formation_for_stop_with_rules(train, stop_index, RuleSet::builtin())
+}
+
+pub fn parse_formation_for_stop_with_rules(
+    train: &FormationResponse,
+    stop_index: usize,
+    rules: &RuleSet,
+) -> Vec<Vehicle> {
     let stop = &train.formations_at_scheduled_stops[stop_index];
     let stop_uic = stop.scheduled_stop.stop_point.uic;
 
     let mut vehicles =
         parse_formation_short_string_raw(&stop.formation_short.formation_short_string);
 
-    let deklassiert_map = deklassiert_by_number(train);
-    let identifiers_pos = identifiers_by_position(train);
-    let number_positions = positions_by_number(train);
+    let trolley_status_map = trolley_status_by_number(train);
     let sectors_pos = sectors_by_position_for_stop(train, stop_uic);
     let zero_number_identifiers = zero_number_identifiers_by_position(train);
 
-    let mut first_pos: Option<u32> = None;
-    let mut last_pos: Option<u32> = None;
-    for vehicle in vehicles.iter() {
-        if let Some(num) = vehicle.order_number {
-            if let Some(pos) = number_positions.get(&num).copied() {
-                if first_pos.is_none() {
-                    first_pos = Some(pos);
-                }
-                last_pos = Some(pos);
-            }
-        }
-    }
-    let reversed = match (first_pos, last_pos) {
-        (Some(first), Some(last)) => last < first,
-        _ => false,
-    };
-
     let mut loco_index = 0usize;
     for (index, vehicle) in vehicles.iter_mut().enumerate() {
         if let Some(coach_number) = vehicle.order_number {
-            if let Some((deklassiert, identifier)) = deklassiert_map.get(&coach_number) {
-                if *deklassiert && !vehicle.status.contains(&StatusFlag::Deklassiert) {
-                    vehicle.status.push(StatusFlag::Deklassiert);
-                }
-                if vehicle.vehicle_type == VehicleType::SecondClass
-                    || vehicle.vehicle_type == VehicleType::FirstAndSecondClass
-                    || vehicle.vehicle_type == VehicleType::DiningSecondClass
-                {
-                    if let Some(identifier) = identifier.clone() {
-                        if let (Some(name), Some(evn)) =
-                            (identifier.type_code_name, identifier.evn)
-                        {
-                            // if it is a first class coach (starts with A) but not marked as deklassiert, mark it as deklassiert
-                            if name.starts_with("A")
-                                && !evn.starts_with("93") // astoro coaches have evn starting with 93 but are not deklassiert
-                                && !vehicle.status.contains(&StatusFlag::Deklassiert)
-                            {
-                                vehicle.status.push(StatusFlag::Deklassiert);
-                            }
-                        }
-                    }
-                }
+            if let Some((trolley_status, identifier)) = trolley_status_map.get(&coach_number) {
                 if vehicle.vehicle_identifier.is_none() {
                     vehicle.vehicle_identifier = identifier.clone();
                 }
+                if let Some(verdict) = rules.evaluate(vehicle, trolley_status.as_ref()) {
+                    if !vehicle.status.contains(&StatusFlag::Deklassiert) {
+                        vehicle.status.push(StatusFlag::Deklassiert);
+                    }
+                    vehicle.deklassiert = Some(verdict);
+                }
             }
         } else if matches!(vehicle.vehicle_type, VehicleType::Locomotive) {
             if zero_number_identifiers.len() == 1 {
@@ -518,9 +571,46 @@ pub fn parse_formation_for_stop(train: &FormationResponse, stop_index: usize) ->
         }
     }
 
+    let destinations = vehicle_destinations(&stop.formation_short, &vehicles);
+    for (vehicle, destination) in vehicles.iter_mut().zip(destinations) {
+        vehicle.destination = destination;
+    }
+
     vehicles
 }
 
+/// Destination of each vehicle in `vehicles`, parsed from `formation.formation_short_string`.
+///
+/// `vehicleGoals` refer to positions in the running train: the first vehicle
+/// of the string that is neither fictional (`F`) nor closed (`-`) is
+/// position 1, and fictional and closed vehicles are not counted. They get
+/// `None`, as do running vehicles that no goal covers.
+pub fn vehicle_destinations(
+    formation: &FormationShort,
+    vehicles: &[Vehicle],
+) -> Vec<Option<StopPoint>> {
+    let mut position = 0u32;
+    vehicles
+        .iter()
+        .map(|vehicle| {
+            if vehicle.vehicle_type == VehicleType::Fictional
+                || vehicle.status.contains(&StatusFlag::Closed)
+            {
+                return None;
+            }
+            position += 1;
+            formation
+                .vehicle_goals
+                .iter()
+                .find(|goal| {
+                    (goal.from_vehicle_at_position..=goal.to_vehicle_at_position)
+                        .contains(&position)
+                })
+                .map(|goal| goal.destination_stop_point.clone())
+        })
+        .collect()
+}
+
 fn parse_vehicle(raw: &str, sector: Option<char>) -> Option<Vehicle> {
     if raw.is_empty() {
         return None;
@@ -563,17 +653,9 @@ fn parse_vehicle(raw: &str, sector: Option<char>) -> Option<Vehicle> {
     let no_passage_left = vehicle_part_raw.contains('(') || offers_part.contains('(');
     let no_passage_right = vehicle_part_raw.contains(')') || offers_part.contains(')');
 
-    let vehicle_part_clean = vehicle_part_raw
-        .replace('(', "")
-        .replace(')', "")
-        .trim()
-        .to_string();
+    let vehicle_part_clean = vehicle_part_raw.replace(['(', ')'], "").trim().to_string();
 
-    let offers_part_clean = offers_part
-        .replace('(', "")
-        .replace(')', "")
-        .trim()
-        .to_string();
+    let offers_part_clean = offers_part.replace(['(', ')'], "").trim().to_string();
 
     let (vehicle_type_str, order_number) =
         if let Some((typ, ord)) = vehicle_part_clean.split_once(':') {
@@ -603,43 +685,55 @@ fn parse_vehicle(raw: &str, sector: Option<char>) -> Option<Vehicle> {
         order_number,
         offers,
         vehicle_identifier: None,
+        traction_unit: None,
+        destination: None,
+        deklassiert: None,
     })
 }
 
 #[cfg(feature = "native-client")]
 pub fn get_train_formation(
+    operator: &Operator,
     train_id: i32,
     year: i32,
     month: u32,
     day: u32,
     token: &str,
-) -> Result<FormationResponse, String> {
-    let base_url = "https://api.opentransportdata.swiss/formation/v2";
-
+) -> Result<FormationResponse, OtdError> {
     let url = format!(
-        "{}/formations_full?evu=SBBP&operationDate={}-{}-{}&trainNumber={}",
-        base_url, year, month, day, train_id
+        "{}/formations_full?evu={}&operationDate={}-{}-{}&trainNumber={}",
+        FORMATION_BASE_URL, operator, year, month, day, train_id
     );
 
     let client = reqwest::blocking::Client::new();
-    let response = client
-        .get(&url)
-        .header("Authorization", token)
-        .send()
-        .map_err(|e| format!("HTTP request failed: {}", e))?;
-
-    if !response.status().is_success() {
-        return Err(format!(
-            "API request failed with status: {}",
-            response.status()
-        ));
-    }
+    let response = client.get(&url).header("Authorization", token).send()?;
+    let response = check_status(response)?;
 
-    let json_text = response
-        .text()
-        .map_err(|e| format!("Failed to read response text: {}", e))?;
+    let json_text = response.text()?;
 
-    parse_formation_json(&json_text).map_err(|e| format!("JSON parsing error: {}", e))
+    let mut formation = parse_formation_json(&json_text)?;
+    formation.operator = operator.clone();
+    Ok(formation)
+}
+
+/// Turn a non-success response into [`OtdError::Http`], keeping the `Retry-After` delay.
+#[cfg(feature = "native-client")]
+fn check_status(
+    response: reqwest::blocking::Response,
+) -> Result<reqwest::blocking::Response, OtdError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_retry_after(v, chrono::Utc::now()));
+    Err(OtdError::Http {
+        status: status.as_u16(),
+        retry_after,
+    })
 }
 
 /// Strip namespace declarations and prefixes.
@@ -654,244 +748,121 @@ fn strip_namespaces(xml: &str) -> String {
     s.to_string()
 }
 
-fn handle_text(
-    text: &str,
-    elem_stack: &Vec<String>,
-    current_train_number: &mut Option<String>,
-    current_departure_time: &mut Option<DateTime<chrono::Utc>>,
-    current_latest_arrival: &mut Option<DateTime<chrono::Utc>>,
-    in_service_departure: bool,
-    in_service_arrival: bool,
-) {
-    if text.is_empty() {
-        return;
-    }
-
-    if let Some(current) = elem_stack.last() {
-        let lower = current.to_lowercase();
-
-        if lower.ends_with("trainnumber") || lower.ends_with("operatingnumber") {
-            *current_train_number = Some(text.to_string());
-        }
-
-        if in_service_departure && lower.ends_with("timetabledtime") {
-            if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
-                *current_departure_time = Some(dt.with_timezone(&chrono::Utc));
-            }
-        }
+/// Train numbers departing within `query`'s window, skipping trains that
+/// finished before `query.active_after`.
+pub fn parse_train_numbers(xml: &str, query: &DiscoveryQuery) -> Result<Vec<String>, OtdError> {
+    let numbers = parse_stop_events(xml)?
+        .into_iter()
+        .filter(|event| query.is_active(event))
+        .filter_map(|event| event.service.train_number)
+        .collect::<BTreeSet<String>>();
+    Ok(numbers.into_iter().collect())
+}
 
-        if in_service_arrival && lower.ends_with("timetabledtime") {
-            if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
-                let dt = dt.with_timezone(&chrono::Utc);
-                let replace = current_latest_arrival.map_or(true, |cur| dt > cur);
-                if replace {
-                    *current_latest_arrival = Some(dt);
-                }
-            }
-        }
+/// Blocking [`OjpClient::fetch_train_numbers`].
+#[cfg(feature = "native-client")]
+pub fn fetch_train_numbers(token: &str) -> Result<Vec<i32>, OtdError> {
+    let query = DiscoveryQuery::today();
+    let client = reqwest::blocking::Client::builder().build()?;
+    let mut results = Vec::new();
+    for hub in query.hubs.iter() {
+        let resp = client
+            .post(OJP_URL)
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/xml")
+            .body(query.request_body(hub))
+            .send()?;
+        let resp = check_status(resp)?;
+        results.push(query.train_numbers(&resp.text()?)?);
     }
+    Ok(discovery::merge_train_numbers(results))
 }
 
-/// Event-driven parser that keeps an element stack and collects unique train numbers.
-/// Works across quick-xml versions by avoiding methods that may not exist.
-pub fn parse_train_numbers(xml: &str) -> Vec<String> {
-    let cleaned = strip_namespaces(xml);
-    let mut reader = Reader::from_str(&cleaned);
-
-    let mut buf = Vec::new();
-    let mut elem_stack: Vec<String> = Vec::new();
-    let mut numbers: BTreeSet<String> = BTreeSet::new();
-
-    let now_utc = chrono::Utc::now();
-    let tz = chrono_tz::Europe::Zurich;
-    let today_local = now_utc.with_timezone(&tz).date_naive();
-    let tomorrow_local = today_local.succ_opt().unwrap();
-    let end_of_today = tz
-        .with_ymd_and_hms(
-            tomorrow_local.year(),
-            tomorrow_local.month(),
-            tomorrow_local.day(),
-            4,
-            0,
-            0,
-        )
-        .single()
-        .unwrap()
-        .with_timezone(&chrono::Utc);
-
-    // per StopEvent state
-    let mut current_train_number: Option<String> = None;
-    let mut current_departure_time: Option<DateTime<chrono::Utc>> = None;
-    let mut current_latest_arrival: Option<DateTime<chrono::Utc>> = None;
-    let mut in_this_call = false;
-    let mut in_service_departure = false;
-    let mut in_service_arrival = false;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(e)) => {
-                let name = str::from_utf8(e.local_name().as_ref())
-                    .unwrap_or("")
-                    .to_string();
-
-                match name.as_str() {
-                    "StopEvent" => {
-                        current_train_number = None;
-                        current_departure_time = None;
-                        current_latest_arrival = None;
-                    }
-                    "ThisCall" => in_this_call = true,
-                    "ServiceDeparture" if in_this_call => in_service_departure = true,
-                    "ServiceArrival" => in_service_arrival = true,
-                    _ => {}
-                }
-
-                elem_stack.push(name);
-            }
-
-            Ok(Event::End(e)) => {
-                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
-
-                match name.as_str() {
-                    "StopEvent" => {
-                        if let (Some(train), Some(dep_time)) =
-                            (&current_train_number, &current_departure_time)
-                        {
-                            if *dep_time <= end_of_today {
-                                let has_future_arrival = current_latest_arrival
-                                    .map(|latest| latest > now_utc)
-                                    .unwrap_or(true);
-                                if has_future_arrival {
-                                    numbers.insert(train.clone());
-                                }
-                            }
-                        }
-                    }
-                    "ThisCall" => in_this_call = false,
-                    "ServiceDeparture" => in_service_departure = false,
-                    "ServiceArrival" => in_service_arrival = false,
-                    _ => {}
-                }
-
-                elem_stack.pop();
-            }
+    fn fixture() -> FormationResponse {
+        parse_formation_json(include_str!("../test_data/test_response.json")).unwrap()
+    }
 
-            Ok(Event::Text(e)) => {
-                let text = String::from_utf8_lossy(e.as_ref()).trim().to_string();
-                handle_text(
-                    &text,
-                    &elem_stack,
-                    &mut current_train_number,
-                    &mut current_departure_time,
-                    &mut current_latest_arrival,
-                    in_service_departure,
-                    in_service_arrival,
-                );
-            }
+    fn destinations(formation: &FormationShort) -> Vec<Option<String>> {
+        let vehicles = parse_formation_short_string_raw(&formation.formation_short_string);
+        vehicle_destinations(formation, &vehicles)
+            .into_iter()
+            .map(|stop| stop.map(|stop| stop.name))
+            .collect()
+    }
 
-            Ok(Event::CData(e)) => {
-                let text = String::from_utf8_lossy(e.as_ref()).trim().to_string();
-                handle_text(
-                    &text,
-                    &elem_stack,
-                    &mut current_train_number,
-                    &mut current_departure_time,
-                    &mut current_latest_arrival,
-                    in_service_departure,
-                    in_service_arrival,
-                );
-            }
+    #[test]
+    fn destinations_skip_fictional_and_closed() {
+        let train = fixture();
+        // Brig: `@A,F,-(LK,-1,-2,-2@B,-2),[(FA:9,...,LK)],F` with goal 1..10.
+        let brig = &train.formations_at_scheduled_stops[0].formation_short;
+        let romanshorn = Some("Romanshorn".to_string());
+        let mut expected = vec![None; 6];
+        expected.extend(vec![romanshorn; 10]);
+        expected.push(None);
+        assert_eq!(destinations(brig), expected);
+    }
 
-            Ok(Event::Eof) => break,
+    #[test]
+    fn vehicles_after_the_last_goal_have_no_destination() {
+        let mut brig = fixture().formations_at_scheduled_stops[0]
+            .formation_short
+            .clone();
+        brig.vehicle_goals[0].to_vehicle_at_position = 8;
+        let destinations = destinations(&brig);
+        let running: Vec<_> = destinations[6..16].iter().collect();
+        assert!(running[..8].iter().all(|stop| stop.is_some()));
+        assert_eq!(running[8..], [&None, &None]);
+    }
 
-            Err(err) => {
-                eprintln!("XML parse error: {}", err);
-                break;
-            }
+    #[test]
+    fn destinations_per_goal() {
+        let formation = FormationShort {
+            formation_short_string: "F,[(1:1,2:2)],-2:3,[(2:4,2:5)]".to_string(),
+            vehicle_goals: vec![
+                VehicleGoal {
+                    from_vehicle_at_position: 1,
+                    to_vehicle_at_position: 2,
+                    destination_stop_point: StopPoint {
+                        uic: 8507492,
+                        name: "Interlaken Ost".to_string(),
+                    },
+                },
+                VehicleGoal {
+                    from_vehicle_at_position: 3,
+                    to_vehicle_at_position: 4,
+                    destination_stop_point: StopPoint {
+                        uic: 8501609,
+                        name: "Brig".to_string(),
+                    },
+                },
+            ],
+        };
+        let interlaken = Some("Interlaken Ost".to_string());
+        let brig = Some("Brig".to_string());
+        assert_eq!(
+            destinations(&formation),
+            vec![
+                None,
+                interlaken.clone(),
+                interlaken,
+                None,
+                brig.clone(),
+                brig
+            ]
+        );
+    }
 
-            _ => {}
+    #[test]
+    fn parse_for_stop_sets_destinations() {
+        let train = fixture();
+        let vehicles = parse_formation_for_stop(&train, 1);
+        for vehicle in vehicles.iter() {
+            let expected = vehicle.vehicle_type != VehicleType::Fictional;
+            assert_eq!(vehicle.destination.is_some(), expected, "{vehicle:?}");
         }
-
-        buf.clear();
     }
-
-    numbers.into_iter().collect()
-}
-
-#[cfg(feature = "native-client")]
-pub fn fetch_train_numbers(token: &str) -> Result<Vec<i32>, Box<dyn std::error::Error>> {
-    let url = "https://api.opentransportdata.swiss/ojp20";
-
-    let now = chrono::Utc::now();
-    let tz = chrono_tz::Europe::Zurich;
-    let today_local = now.with_timezone(&tz).date_naive();
-    let start_local = tz
-        .with_ymd_and_hms(
-            today_local.year(),
-            today_local.month(),
-            today_local.day(),
-            4,
-            0,
-            0,
-        )
-        .single()
-        .ok_or("invalid local start time")?;
-    let start_time = start_local
-        .with_timezone(&chrono::Utc)
-        .format("%Y-%m-%dT%H:%M:%SZ")
-        .to_string();
-
-    // Simple XML similar to the Python example; adjust StopPointRef / params as needed.
-    let xml_body = format!(
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-        <OJP xmlns="http://www.vdv.de/ojp" xmlns:siri="http://www.siri.org.uk/siri" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" version="2.0">
-          <OJPRequest>
-            <siri:ServiceRequest>
-              <siri:ServiceRequestContext>
-                <siri:Language>de</siri:Language>
-              </siri:ServiceRequestContext>
-              <siri:RequestTimestamp>{}</siri:RequestTimestamp>
-              <siri:RequestorRef>MyApp</siri:RequestorRef>
-              <OJPStopEventRequest>
-                <siri:RequestTimestamp>{}</siri:RequestTimestamp>
-                <siri:MessageIdentifier>SER_1</siri:MessageIdentifier>
-                <Location>
-                  <PlaceRef>
-                    <siri:StopPointRef>8507000</siri:StopPointRef>
-                    <Name><Text>Bern</Text></Name>
-                  </PlaceRef>
-                  <DepArrTime>{}</DepArrTime>
-                </Location>
-                <Params>
-                  <NumberOfResults>1000</NumberOfResults>
-                  <StopEventType>departure</StopEventType>
-                  <IncludePreviousCalls>true</IncludePreviousCalls>
-                  <IncludeOnwardCalls>true</IncludeOnwardCalls>
-                  <UseRealtimeData>full</UseRealtimeData>
-                </Params>
-              </OJPStopEventRequest>
-            </siri:ServiceRequest>
-          </OJPRequest>
-        </OJP>
-        "#,
-        start_time, start_time, start_time
-    );
-
-    let client = reqwest::blocking::Client::builder().build()?;
-    let resp = client
-        .post(url)
-        .header("Authorization", format!("Bearer {}", token))
-        .header("Content-Type", "application/xml")
-        .body(xml_body)
-        .send()?;
-
-    let text = resp.text()?;
-    let trains = parse_train_numbers(&text)
-        .iter()
-        .map(|n| n.parse::<i32>().unwrap())
-        .filter(|n| {
-            (*n >= 600 && *n <= 649) || (*n >= 800 && *n <= 849) || (*n >= 950 && *n <= 999)
-        }) // filter for IC8/81, IC6/61
-        .collect::<Vec<i32>>();
-    Ok(trains)
 }
@@ -0,0 +1,188 @@
+use std::path::Path;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{FormationResponse, Operator, OtdError, TrainId};
+
+const DEFAULT_LINES: &str = include_str!("../catalog/lines.toml");
+
+static DEFAULT_LINE_CATALOG: LazyLock<LineCatalog> =
+    LazyLock::new(|| LineCatalog::from_toml(DEFAULT_LINES).expect("built-in lines are valid"));
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Line {
+    /// Short id, e.g. "IC61".
+    pub id: String,
+    pub display_name: String,
+    /// Inclusive train number ranges.
+    pub numbers: Vec<[i32; 2]>,
+    /// Stations the route must call at, by name.
+    #[serde(default)]
+    pub route_signature: Vec<String>,
+    /// Name of the SBB icon shown as the logo, e.g. "ic-81".
+    #[serde(default)]
+    pub logo: Option<String>,
+    /// Train numbers are only unique per operator.
+    #[serde(default)]
+    pub operator: Operator,
+}
+
+/// The lines we monitor, loaded from TOML.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineCatalog {
+    #[serde(rename = "line", default)]
+    pub lines: Vec<Line>,
+}
+
+impl Line {
+    pub fn contains_number(&self, train_number: i32) -> bool {
+        self.numbers
+            .iter()
+            .any(|[from, to]| (*from..=*to).contains(&train_number))
+    }
+
+    /// Whether the line is run by the train's operator and covers its number.
+    pub fn covers(&self, train: &TrainId) -> bool {
+        self.operator == train.operator && self.contains_number(train.train_number)
+    }
+
+    /// Whether a route calling at `stops` matches the signature.
+    pub fn matches_route<S: AsRef<str>>(&self, stops: &[S]) -> bool {
+        self.route_signature
+            .iter()
+            .all(|station| stops.iter().any(|stop| stop.as_ref() == station))
+    }
+}
+
+impl Default for LineCatalog {
+    /// The lines in `catalog/lines.toml`.
+    fn default() -> Self {
+        DEFAULT_LINE_CATALOG.clone()
+    }
+}
+
+impl LineCatalog {
+    pub fn from_toml(input: &str) -> Result<Self, OtdError> {
+        toml::from_str(input).map_err(|e| OtdError::Config(e.to_string()))
+    }
+
+    /// Read the catalog from a TOML file in the format of
+    /// `catalog/lines.toml`.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, OtdError> {
+        let path = path.as_ref();
+        let input = std::fs::read_to_string(path)
+            .map_err(|e| OtdError::Config(format!("{}: {}", path.display(), e)))?;
+        toml::from_str(&input).map_err(|e| OtdError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// The built-in catalog, without cloning it.
+    pub fn builtin() -> &'static LineCatalog {
+        &DEFAULT_LINE_CATALOG
+    }
+
+    pub fn by_id(&self, id: &str) -> Option<&Line> {
+        self.lines.iter().find(|line| line.id == id)
+    }
+
+    /// Whether a line of the train's operator covers its number, regardless
+    /// of route.
+    pub fn contains(&self, train: &TrainId) -> bool {
+        self.lines.iter().any(|line| line.covers(train))
+    }
+
+    /// First line of the train's operator covering its number whose
+    /// signature the route matches.
+    pub fn line_for<S: AsRef<str>>(&self, train: &TrainId, stops: &[S]) -> Option<&Line> {
+        self.lines
+            .iter()
+            .find(|line| line.covers(train) && line.matches_route(stops))
+    }
+
+    /// The operator of the lines covering `train_number` on a route calling
+    /// at `stops`, for services whose operator is not known otherwise.
+    /// `None` if no line or lines of several operators match.
+    pub fn operator_for<S: AsRef<str>>(&self, train_number: i32, stops: &[S]) -> Option<&Operator> {
+        let mut operators = self
+            .lines
+            .iter()
+            .filter(|line| line.contains_number(train_number) && line.matches_route(stops))
+            .map(|line| &line.operator);
+        let operator = operators.next()?;
+        operators.all(|other| other == operator).then_some(operator)
+    }
+
+    pub fn line_for_train(&self, train: &FormationResponse) -> Option<&Line> {
+        let stops: Vec<&str> = train
+            .formations_at_scheduled_stops
+            .iter()
+            .map(|stop| stop.scheduled_stop.stop_point.name.as_str())
+            .collect();
+        self.line_for(&train.train_id(), &stops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINES: &str = r#"
+        [[line]]
+        id = "IC61"
+        display_name = "IC 61"
+        numbers = [[950, 999]]
+        route_signature = ["Interlaken Ost"]
+
+        [[line]]
+        id = "IC6"
+        display_name = "IC 6"
+        numbers = [[950, 999]]
+
+        [[line]]
+        id = "RE"
+        display_name = "RE"
+        numbers = [[950, 999]]
+        operator = "BLSP"
+    "#;
+
+    #[test]
+    fn lookups_are_per_operator() {
+        let catalog = LineCatalog::from_toml(LINES).unwrap();
+        let sbb = TrainId::new(Operator::Sbb, 965);
+        let bls = TrainId::new(Operator::Bls, 965);
+        assert!(catalog.contains(&sbb));
+        assert!(!catalog.contains(&TrainId::new(Operator::Sob, 965)));
+        assert_eq!(catalog.line_for(&sbb, &["Bern"]).unwrap().id, "IC6");
+        assert_eq!(
+            catalog.line_for(&sbb, &["Interlaken Ost"]).unwrap().id,
+            "IC61"
+        );
+        assert_eq!(
+            catalog.line_for(&bls, &["Interlaken Ost"]).unwrap().id,
+            "RE"
+        );
+    }
+
+    #[test]
+    fn operator_from_the_catalog() {
+        let catalog = LineCatalog::from_toml(LINES).unwrap();
+        assert_eq!(catalog.operator_for(965, &["Bern"]), None);
+        let sbb_only = LineCatalog {
+            lines: catalog.lines[..2].to_vec(),
+        };
+        assert_eq!(sbb_only.operator_for(965, &["Bern"]), Some(&Operator::Sbb));
+        assert_eq!(sbb_only.operator_for(1965, &["Bern"]), None);
+    }
+
+    #[test]
+    fn catalog_from_path() {
+        let catalog =
+            LineCatalog::from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/catalog/lines.toml"))
+                .unwrap();
+        assert_eq!(&catalog, LineCatalog::builtin());
+        assert!(matches!(
+            LineCatalog::from_path("missing.toml"),
+            Err(OtdError::Config(message)) if message.starts_with("missing.toml: ")
+        ));
+    }
+}
@@ -0,0 +1,439 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use quick_xml::Writer;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+
+const OJP_NAMESPACE: &str = "http://www.vdv.de/ojp";
+const SIRI_NAMESPACE: &str = "http://www.siri.org.uk/siri";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopEventType {
+    Departure,
+    Arrival,
+    Both,
+}
+
+impl StopEventType {
+    fn as_str(self) -> &'static str {
+        match self {
+            StopEventType::Departure => "departure",
+            StopEventType::Arrival => "arrival",
+            StopEventType::Both => "both",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RealtimeData {
+    /// Estimated times and situations.
+    Full,
+    /// Situations only.
+    Explanatory,
+    /// Timetable only.
+    None,
+}
+
+impl RealtimeData {
+    fn as_str(self) -> &'static str {
+        match self {
+            RealtimeData::Full => "full",
+            RealtimeData::Explanatory => "explanatory",
+            RealtimeData::None => "none",
+        }
+    }
+}
+
+/// The `ServiceRequest` fields every OJP request carries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestContext {
+    pub language: String,
+    pub requestor_ref: String,
+    pub message_identifier: String,
+    /// `None` for the time the XML is written.
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl Default for RequestContext {
+    fn default() -> Self {
+        RequestContext {
+            language: "de".to_string(),
+            requestor_ref: concat!("opentransportdata_", env!("CARGO_PKG_VERSION")).to_string(),
+            message_identifier: "1".to_string(),
+            timestamp: None,
+        }
+    }
+}
+
+/// OJP 2.0 `OJPStopEventRequest`: departures and/or arrivals at one stop.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StopEventRequest {
+    pub context: RequestContext,
+    pub stop_point_ref: String,
+    pub stop_name: Option<String>,
+    pub dep_arr_time: DateTime<Utc>,
+    pub event_type: StopEventType,
+    pub number_of_results: u32,
+    pub include_previous_calls: bool,
+    pub include_onward_calls: bool,
+    pub realtime: RealtimeData,
+}
+
+impl StopEventRequest {
+    /// Departures at `stop_point_ref` from `at` on, with all calls and
+    /// realtime data.
+    pub fn new(stop_point_ref: impl Into<String>, at: DateTime<Utc>) -> Self {
+        StopEventRequest {
+            context: RequestContext::default(),
+            stop_point_ref: stop_point_ref.into(),
+            stop_name: None,
+            dep_arr_time: at,
+            event_type: StopEventType::Departure,
+            number_of_results: 100,
+            include_previous_calls: true,
+            include_onward_calls: true,
+            realtime: RealtimeData::Full,
+        }
+    }
+
+    pub fn stop_name(mut self, name: impl Into<String>) -> Self {
+        self.stop_name = Some(name.into());
+        self
+    }
+
+    pub fn event_type(mut self, event_type: StopEventType) -> Self {
+        self.event_type = event_type;
+        self
+    }
+
+    pub fn number_of_results(mut self, results: u32) -> Self {
+        self.number_of_results = results;
+        self
+    }
+
+    pub fn previous_calls(mut self, include: bool) -> Self {
+        self.include_previous_calls = include;
+        self
+    }
+
+    pub fn onward_calls(mut self, include: bool) -> Self {
+        self.include_onward_calls = include;
+        self
+    }
+
+    pub fn realtime(mut self, realtime: RealtimeData) -> Self {
+        self.realtime = realtime;
+        self
+    }
+
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.context.language = language.into();
+        self
+    }
+
+    pub fn requestor_ref(mut self, requestor_ref: impl Into<String>) -> Self {
+        self.context.requestor_ref = requestor_ref.into();
+        self
+    }
+
+    /// Fixed request timestamp instead of the time the XML is written.
+    pub fn timestamp(mut self, at: DateTime<Utc>) -> Self {
+        self.context.timestamp = Some(at);
+        self
+    }
+
+    pub fn to_xml(&self) -> String {
+        ojp_envelope(&self.context, |xml| {
+            let timestamp = xml.timestamp.clone();
+            xml.start("OJPStopEventRequest");
+            xml.element("siri:RequestTimestamp", &timestamp);
+            xml.element("siri:MessageIdentifier", &self.context.message_identifier);
+            xml.start("Location");
+            xml.start("PlaceRef");
+            xml.element("siri:StopPointRef", &self.stop_point_ref);
+            if let Some(name) = self.stop_name.as_ref() {
+                xml.start("Name");
+                xml.element("Text", name);
+                xml.end("Name");
+            }
+            xml.end("PlaceRef");
+            xml.element("DepArrTime", &format_time(self.dep_arr_time));
+            xml.end("Location");
+            xml.start("Params");
+            xml.element("NumberOfResults", &self.number_of_results.to_string());
+            xml.element("StopEventType", self.event_type.as_str());
+            xml.element(
+                "IncludePreviousCalls",
+                &self.include_previous_calls.to_string(),
+            );
+            xml.element("IncludeOnwardCalls", &self.include_onward_calls.to_string());
+            xml.element("UseRealtimeData", self.realtime.as_str());
+            xml.end("Params");
+            xml.end("OJPStopEventRequest");
+        })
+    }
+}
+
+/// OJP 2.0 `OJPTripInfoRequest`: all calls of one journey.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TripInfoRequest {
+    pub context: RequestContext,
+    /// E.g. `ch:1:sjyid:100001:825-001`, see [`crate::Service::journey_ref`].
+    pub journey_ref: String,
+    pub operating_day: NaiveDate,
+    pub include_calls: bool,
+    pub include_service: bool,
+    pub realtime: RealtimeData,
+}
+
+impl TripInfoRequest {
+    /// All calls and the service, with realtime data.
+    pub fn new(journey_ref: impl Into<String>, operating_day: NaiveDate) -> Self {
+        TripInfoRequest {
+            context: RequestContext::default(),
+            journey_ref: journey_ref.into(),
+            operating_day,
+            include_calls: true,
+            include_service: true,
+            realtime: RealtimeData::Full,
+        }
+    }
+
+    pub fn calls(mut self, include: bool) -> Self {
+        self.include_calls = include;
+        self
+    }
+
+    pub fn service(mut self, include: bool) -> Self {
+        self.include_service = include;
+        self
+    }
+
+    pub fn realtime(mut self, realtime: RealtimeData) -> Self {
+        self.realtime = realtime;
+        self
+    }
+
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.context.language = language.into();
+        self
+    }
+
+    pub fn requestor_ref(mut self, requestor_ref: impl Into<String>) -> Self {
+        self.context.requestor_ref = requestor_ref.into();
+        self
+    }
+
+    /// Fixed request timestamp instead of the time the XML is written.
+    pub fn timestamp(mut self, at: DateTime<Utc>) -> Self {
+        self.context.timestamp = Some(at);
+        self
+    }
+
+    pub fn to_xml(&self) -> String {
+        ojp_envelope(&self.context, |xml| {
+            let timestamp = xml.timestamp.clone();
+            xml.start("OJPTripInfoRequest");
+            xml.element("siri:RequestTimestamp", &timestamp);
+            xml.element("siri:MessageIdentifier", &self.context.message_identifier);
+            xml.element("JourneyRef", &self.journey_ref);
+            xml.element(
+                "OperatingDayRef",
+                &self.operating_day.format("%Y-%m-%d").to_string(),
+            );
+            xml.start("Params");
+            xml.element("UseRealtimeData", self.realtime.as_str());
+            xml.element("IncludeCalls", &self.include_calls.to_string());
+            xml.element("IncludeService", &self.include_service.to_string());
+            xml.end("Params");
+            xml.end("OJPTripInfoRequest");
+        })
+    }
+}
+
+/// What a [`LocationInformationRequest`] looks up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocationInput {
+    /// Free text, e.g. `"Bern"`.
+    Name(String),
+    /// A stop place by its reference (the UIC number in Switzerland).
+    StopPlaceRef(String),
+}
+
+/// OJP 2.0 `OJPLocationInformationRequest`, restricted to stops.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocationInformationRequest {
+    pub context: RequestContext,
+    pub input: LocationInput,
+    pub number_of_results: u32,
+}
+
+impl LocationInformationRequest {
+    /// Stops whose name matches `name`.
+    pub fn by_name(name: impl Into<String>) -> Self {
+        LocationInformationRequest {
+            context: RequestContext::default(),
+            input: LocationInput::Name(name.into()),
+            number_of_results: 10,
+        }
+    }
+
+    pub fn by_uic(uic: u32) -> Self {
+        LocationInformationRequest {
+            context: RequestContext::default(),
+            input: LocationInput::StopPlaceRef(uic.to_string()),
+            number_of_results: 1,
+        }
+    }
+
+    pub fn number_of_results(mut self, results: u32) -> Self {
+        self.number_of_results = results;
+        self
+    }
+
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.context.language = language.into();
+        self
+    }
+
+    pub fn requestor_ref(mut self, requestor_ref: impl Into<String>) -> Self {
+        self.context.requestor_ref = requestor_ref.into();
+        self
+    }
+
+    /// Fixed request timestamp instead of the time the XML is written.
+    pub fn timestamp(mut self, at: DateTime<Utc>) -> Self {
+        self.context.timestamp = Some(at);
+        self
+    }
+
+    pub fn to_xml(&self) -> String {
+        ojp_envelope(&self.context, |xml| {
+            let timestamp = xml.timestamp.clone();
+            xml.start("OJPLocationInformationRequest");
+            xml.element("siri:RequestTimestamp", &timestamp);
+            xml.element("siri:MessageIdentifier", &self.context.message_identifier);
+            match &self.input {
+                LocationInput::Name(name) => {
+                    xml.start("InitialInput");
+                    xml.element("Name", name);
+                    xml.end("InitialInput");
+                }
+                LocationInput::StopPlaceRef(stop_place) => {
+                    xml.start("PlaceRef");
+                    xml.element("StopPlaceRef", stop_place);
+                    xml.end("PlaceRef");
+                }
+            }
+            xml.start("Restrictions");
+            xml.element("Type", "stop");
+            xml.element("NumberOfResults", &self.number_of_results.to_string());
+            xml.end("Restrictions");
+            xml.end("OJPLocationInformationRequest");
+        })
+    }
+}
+
+/// Writes escaped XML for request bodies.
+pub(crate) struct XmlBuilder {
+    writer: Writer<Vec<u8>>,
+    /// Request timestamp, shared by the envelope and the request element.
+    pub(crate) timestamp: String,
+}
+
+impl XmlBuilder {
+    pub(crate) fn start(&mut self, name: &str) {
+        self.write(Event::Start(BytesStart::new(name)));
+    }
+
+    pub(crate) fn end(&mut self, name: &str) {
+        self.write(Event::End(BytesEnd::new(name)));
+    }
+
+    pub(crate) fn element(&mut self, name: &str, text: &str) {
+        self.start(name);
+        self.write(Event::Text(BytesText::new(text)));
+        self.end(name);
+    }
+
+    fn write(&mut self, event: Event<'_>) {
+        self.writer
+            .write_event(event)
+            .expect("writing to a Vec does not fail");
+    }
+}
+
+/// Wrap the request written by `body` in `OJP/OJPRequest/ServiceRequest`.
+pub(crate) fn ojp_envelope(context: &RequestContext, body: impl FnOnce(&mut XmlBuilder)) -> String {
+    let mut xml = XmlBuilder {
+        writer: Writer::new_with_indent(Vec::new(), b' ', 2),
+        timestamp: format_time(context.timestamp.unwrap_or_else(Utc::now)),
+    };
+    xml.write(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)));
+
+    let mut root = BytesStart::new("OJP");
+    root.push_attribute(("xmlns", OJP_NAMESPACE));
+    root.push_attribute(("xmlns:siri", SIRI_NAMESPACE));
+    root.push_attribute(("version", "2.0"));
+    xml.write(Event::Start(root));
+    xml.start("OJPRequest");
+    xml.start("siri:ServiceRequest");
+    xml.start("siri:ServiceRequestContext");
+    xml.element("siri:Language", &context.language);
+    xml.end("siri:ServiceRequestContext");
+    let timestamp = xml.timestamp.clone();
+    xml.element("siri:RequestTimestamp", &timestamp);
+    xml.element("siri:RequestorRef", &context.requestor_ref);
+    body(&mut xml);
+    xml.end("siri:ServiceRequest");
+    xml.end("OJPRequest");
+    xml.end("OJP");
+
+    String::from_utf8(xml.writer.into_inner()).expect("XML is written from str")
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at() -> DateTime<Utc> {
+        "2026-01-31T03:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn stop_event_request_xml() {
+        let xml = StopEventRequest::new("8507000", at())
+            .stop_name("Bern")
+            .number_of_results(50)
+            .requestor_ref("deklassiert_test")
+            .timestamp(at())
+            .to_xml();
+        assert_eq!(xml, include_str!("../test_data/stop_event_request.xml"));
+    }
+
+    #[test]
+    fn trip_info_request_xml() {
+        let day = NaiveDate::from_ymd_opt(2026, 1, 31).unwrap();
+        let xml = TripInfoRequest::new("ch:1:sjyid:100001:825-001", day)
+            .service(false)
+            .requestor_ref("deklassiert_test")
+            .timestamp(at())
+            .to_xml();
+        assert_eq!(xml, include_str!("../test_data/trip_info_request.xml"));
+    }
+
+    #[test]
+    fn location_information_request_xml() {
+        let xml = LocationInformationRequest::by_name("Bern & Wankdorf")
+            .number_of_results(3)
+            .requestor_ref("deklassiert_test")
+            .timestamp(at())
+            .to_xml();
+        assert_eq!(
+            xml,
+            include_str!("../test_data/location_information_request.xml")
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Railway undertaking, as the formation API's `evu` parameter knows it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Operator {
+    #[default]
+    Sbb,
+    Bls,
+    Sob,
+    Thurbo,
+    Rhb,
+    Zb,
+    Tpf,
+    Trn,
+    Mbc,
+    Oebb,
+    Vdbb,
+    /// Passed to the API as is.
+    Other(String),
+}
+
+impl Operator {
+    /// The `evu` code, e.g. `SBBP`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Operator::Sbb => "SBBP",
+            Operator::Bls => "BLSP",
+            Operator::Sob => "SOB",
+            Operator::Thurbo => "THURBO",
+            Operator::Rhb => "RhB",
+            Operator::Zb => "ZB",
+            Operator::Tpf => "TPF",
+            Operator::Trn => "TRN",
+            Operator::Mbc => "MBC",
+            Operator::Oebb => "OeBB",
+            Operator::Vdbb => "VDBB",
+            Operator::Other(code) => code,
+        }
+    }
+
+    /// From the transport company number OJP gives as `OperatorRef` and the
+    /// formation API as `toCode`, e.g. `11` for SBB. `None` for numbers
+    /// without a known `evu` code.
+    pub fn from_company_code(code: &str) -> Option<Operator> {
+        let code = code.rsplit(':').next()?.trim_start_matches('0');
+        match code {
+            "11" => Some(Operator::Sbb),
+            "33" => Some(Operator::Bls),
+            "65" => Some(Operator::Thurbo),
+            "72" => Some(Operator::Rhb),
+            "82" => Some(Operator::Sob),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Operator {
+    type Err = std::convert::Infallible;
+
+    /// Case-insensitive; the passenger brands (`SBB`, `BLS`) are accepted
+    /// too.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_ascii_uppercase().as_str() {
+            "SBB" | "SBBP" => Operator::Sbb,
+            "BLS" | "BLSP" => Operator::Bls,
+            "SOB" => Operator::Sob,
+            "THURBO" => Operator::Thurbo,
+            "RHB" => Operator::Rhb,
+            "ZB" => Operator::Zb,
+            "TPF" => Operator::Tpf,
+            "TRN" => Operator::Trn,
+            "MBC" => Operator::Mbc,
+            "OEBB" => Operator::Oebb,
+            "VDBB" => Operator::Vdbb,
+            _ => Operator::Other(s.trim().to_string()),
+        })
+    }
+}
+
+impl From<String> for Operator {
+    fn from(code: String) -> Self {
+        let Ok(operator) = code.parse();
+        operator
+    }
+}
+
+impl From<Operator> for String {
+    fn from(operator: Operator) -> Self {
+        operator.as_str().to_string()
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A train is identified by its number only together with its operator.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TrainId {
+    pub operator: Operator,
+    pub train_number: i32,
+}
+
+impl TrainId {
+    pub fn new(operator: Operator, train_number: i32) -> Self {
+        TrainId {
+            operator,
+            train_number,
+        }
+    }
+
+    pub fn sbb(train_number: i32) -> Self {
+        TrainId::new(Operator::Sbb, train_number)
+    }
+}
+
+impl fmt::Display for TrainId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.operator, self.train_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_company_code() {
+        assert_eq!(Operator::from_company_code("11"), Some(Operator::Sbb));
+        assert_eq!(Operator::from_company_code("000011"), Some(Operator::Sbb));
+        assert_eq!(Operator::from_company_code("ojp:33"), Some(Operator::Bls));
+        assert_eq!(Operator::from_company_code("65"), Some(Operator::Thurbo));
+        assert_eq!(Operator::from_company_code("72"), Some(Operator::Rhb));
+        assert_eq!(Operator::from_company_code("82"), Some(Operator::Sob));
+        assert_eq!(Operator::from_company_code("110"), None);
+        assert_eq!(Operator::from_company_code("0"), None);
+        assert_eq!(Operator::from_company_code(""), None);
+    }
+
+    #[test]
+    fn parse_codes_and_brands() {
+        let parse = |code: &str| code.parse::<Operator>().unwrap();
+        assert_eq!(parse("SBBP"), Operator::Sbb);
+        assert_eq!(parse(" sbb "), Operator::Sbb);
+        assert_eq!(parse("bls"), Operator::Bls);
+        assert_eq!(parse("RhB"), Operator::Rhb);
+        assert_eq!(parse("oebb"), Operator::Oebb);
+        for operator in [
+            Operator::Sbb,
+            Operator::Thurbo,
+            Operator::Rhb,
+            Operator::Oebb,
+        ] {
+            assert_eq!(parse(operator.as_str()), operator);
+        }
+    }
+
+    #[test]
+    fn unknown_and_empty_codes() {
+        let unknown: Operator = " AVA ".parse().unwrap();
+        assert_eq!(unknown, Operator::Other("AVA".to_string()));
+        assert_eq!(unknown.as_str(), "AVA");
+        assert_eq!(
+            Operator::from("".to_string()),
+            Operator::Other(String::new())
+        );
+        assert_eq!(Operator::default(), Operator::Sbb);
+    }
+
+    #[test]
+    fn train_id_display() {
+        assert_eq!(TrainId::sbb(825).to_string(), "SBBP 825");
+        assert_eq!(TrainId::new(Operator::Rhb, 1123).to_string(), "RhB 1123");
+        assert_eq!(
+            TrainId::new(Operator::Other("AVA".to_string()), 7).to_string(),
+            "AVA 7"
+        );
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let id = TrainId::new(Operator::Bls, 3512);
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, r#"{"operator":"BLSP","train_number":3512}"#);
+        assert_eq!(serde_json::from_str::<TrainId>(&json).unwrap(), id);
+
+        let other = TrainId::new(Operator::Other("AVA".to_string()), 7);
+        let json = serde_json::to_string(&other).unwrap();
+        assert_eq!(serde_json::from_str::<TrainId>(&json).unwrap(), other);
+        // brands are read like codes
+        let brand: TrainId =
+            serde_json::from_str(r#"{"operator":"sbb","train_number":825}"#).unwrap();
+        assert_eq!(brand, TrainId::sbb(825));
+    }
+}
@@ -0,0 +1,211 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Token bucket shared by every clone, so one quota can be used from
+/// several clients and tasks.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: u32,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+    /// Set from a `Retry-After` header; nothing is sent before this.
+    paused_until: Option<Instant>,
+    waits: u64,
+    waited: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    /// Requests that can be sent right now without waiting.
+    pub remaining: u32,
+    pub capacity: u32,
+    /// Time left on a `Retry-After` pause.
+    pub paused_for: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LimiterStats {
+    /// Number of times a request was put to sleep by the limiter.
+    pub waits: u64,
+    pub waited: Duration,
+}
+
+impl RateLimiter {
+    /// `capacity` requests per `period`, refilled evenly.
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        let capacity = capacity.max(1);
+        RateLimiter {
+            bucket: Arc::new(Mutex::new(Bucket {
+                capacity,
+                tokens: capacity as f64,
+                refill_per_sec: capacity as f64 / period.as_secs_f64(),
+                last_refill: Instant::now(),
+                paused_until: None,
+                waits: 0,
+                waited: Duration::ZERO,
+            })),
+        }
+    }
+
+    pub fn per_minute(capacity: u32) -> Self {
+        RateLimiter::new(capacity, Duration::from_secs(60))
+    }
+
+    /// Wait until a request may be sent and take one token.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                bucket.refill(now);
+                match bucket.wait_time(now) {
+                    None => {
+                        bucket.tokens -= 1.0;
+                        return;
+                    }
+                    Some(wait) => {
+                        bucket.waits += 1;
+                        bucket.waited += wait;
+                        wait
+                    }
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Hold all requests back for `duration`, e.g. after a `Retry-After`.
+    pub fn pause_for(&self, duration: Duration) {
+        let mut bucket = self.bucket.lock().unwrap();
+        let until = Instant::now() + duration;
+        if bucket.paused_until.is_none_or(|current| current < until) {
+            bucket.paused_until = Some(until);
+        }
+        // One request may go as soon as the pause is over, the rest refills from there.
+        bucket.tokens = bucket.tokens.min(1.0);
+    }
+
+    pub fn budget(&self) -> Budget {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        bucket.refill(now);
+        let paused_for = bucket
+            .paused_until
+            .filter(|until| *until > now)
+            .map(|until| until - now);
+        Budget {
+            remaining: if paused_for.is_some() {
+                0
+            } else {
+                bucket.tokens.max(0.0).floor() as u32
+            },
+            capacity: bucket.capacity,
+            paused_for,
+        }
+    }
+
+    pub fn stats(&self) -> LimiterStats {
+        let bucket = self.bucket.lock().unwrap();
+        LimiterStats {
+            waits: bucket.waits,
+            waited: bucket.waited,
+        }
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        // Nothing refills while paused.
+        let from = match self.paused_until {
+            Some(until) if until > self.last_refill => until.min(now),
+            _ => self.last_refill,
+        };
+        let elapsed = now.saturating_duration_since(from).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity as f64);
+        self.last_refill = now;
+    }
+
+    fn wait_time(&self, now: Instant) -> Option<Duration> {
+        if let Some(until) = self.paused_until
+            && until > now
+        {
+            return Some(until - now);
+        }
+        if self.tokens >= 1.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(
+            (1.0 - self.tokens) / self.refill_per_sec,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_up_to_capacity() {
+        let limiter = RateLimiter::new(3, Duration::from_secs(3));
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(limiter.budget().remaining, 0);
+        assert_eq!(limiter.stats(), LimiterStats::default());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refills_evenly() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(10));
+        limiter.acquire().await;
+        limiter.acquire().await;
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(limiter.budget().remaining, 1);
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_eq!(limiter.budget().remaining, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn counts_waits() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        // Two tokens in the burst, then one every 5 seconds.
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        let stats = limiter.stats();
+        assert_eq!(stats.waits, 2);
+        assert_eq!(stats.waited, Duration::from_secs(10));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pause_holds_requests_back() {
+        let limiter = RateLimiter::new(5, Duration::from_secs(5));
+        limiter.pause_for(Duration::from_secs(30));
+        let budget = limiter.budget();
+        assert_eq!(budget.remaining, 0);
+        assert_eq!(budget.paused_for, Some(Duration::from_secs(30)));
+
+        let start = Instant::now();
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(30));
+        assert_eq!(limiter.stats().waits, 1);
+
+        // A shorter pause does not cut the current one.
+        limiter.pause_for(Duration::from_secs(10));
+        limiter.pause_for(Duration::from_secs(5));
+        assert_eq!(limiter.budget().paused_for, Some(Duration::from_secs(10)));
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+
+/// Responses a client received, shared by every clone.
+#[derive(Debug, Clone, Default)]
+pub struct RequestStats {
+    inner: Arc<Mutex<RequestCounts>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestCounts {
+    /// Responses by HTTP status, retried `429`s included.
+    pub by_status: BTreeMap<u16, u64>,
+    /// Requests that got no response, e.g. timeouts.
+    pub transport_errors: u64,
+    pub last_status: Option<u16>,
+    /// Time of the last `2xx` or `304` response.
+    pub last_success: Option<DateTime<Utc>>,
+}
+
+impl RequestCounts {
+    pub fn total(&self) -> u64 {
+        self.by_status.values().sum::<u64>() + self.transport_errors
+    }
+
+    /// `4xx` and `5xx` responses plus transport errors.
+    pub fn errors(&self) -> u64 {
+        self.by_status
+            .iter()
+            .filter(|(status, _)| **status >= 400)
+            .map(|(_, count)| count)
+            .sum::<u64>()
+            + self.transport_errors
+    }
+}
+
+impl RequestStats {
+    pub fn new() -> Self {
+        RequestStats::default()
+    }
+
+    pub fn counts(&self) -> RequestCounts {
+        self.inner.lock().unwrap().clone()
+    }
+
+    pub(crate) fn record_response(&self, status: u16) {
+        let mut counts = self.inner.lock().unwrap();
+        *counts.by_status.entry(status).or_default() += 1;
+        counts.last_status = Some(status);
+        if (200..300).contains(&status) || status == 304 {
+            counts.last_success = Some(Utc::now());
+        }
+    }
+
+    pub(crate) fn record_transport_error(&self) {
+        self.inner.lock().unwrap().transport_errors += 1;
+    }
+}
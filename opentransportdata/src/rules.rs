@@ -0,0 +1,312 @@
+use std::path::Path;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{OtdError, TrolleyStatus, Vehicle, VehicleIdentifier, parse_vehicle_type};
+
+const DEFAULT_RULES: &str = include_str!("../rules/deklassiert.toml");
+
+static DEFAULT_RULE_SET: LazyLock<RuleSet> =
+    LazyLock::new(|| RuleSet::from_toml(DEFAULT_RULES).expect("built-in rules are valid"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    #[default]
+    Flag,
+    /// Clear the vehicle and skip the remaining rules.
+    Exempt,
+}
+
+/// Facts about a vehicle a rule can test. Empty fields match anything.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Condition {
+    #[serde(default)]
+    pub trolley_status: Vec<TrolleyStatus>,
+    /// Type codes from the formation string, e.g. `"2"` or `"W2"`.
+    #[serde(default)]
+    pub slot: Vec<String>,
+    #[serde(default)]
+    pub type_code_name_prefix: Option<String>,
+    /// Whether the vehicle must have an EVN (`true`) or must not (`false`).
+    #[serde(default)]
+    pub has_evn: Option<bool>,
+    #[serde(default)]
+    pub evn_prefix: Option<String>,
+    /// Any of the listed interoperability codes, read from the EVN's first
+    /// two digits if it does not validate.
+    #[serde(default)]
+    pub evn_interoperability: Vec<u8>,
+    /// Any of the listed UIC country codes, digits 3-4 of the EVN.
+    #[serde(default)]
+    pub evn_country: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub action: Action,
+    pub confidence: Confidence,
+    #[serde(default)]
+    pub when: Condition,
+    #[serde(default)]
+    pub unless: Vec<Condition>,
+}
+
+/// Ordered deklassiert detection rules.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleSet {
+    #[serde(rename = "rule", default)]
+    pub rules: Vec<Rule>,
+}
+
+/// Why a vehicle was flagged.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Verdict {
+    /// Names of the rules that fired, in evaluation order.
+    pub rules: Vec<String>,
+    /// Descriptions of the rules that fired, for display.
+    pub reasons: Vec<String>,
+    /// Highest confidence among the fired rules.
+    pub confidence: Confidence,
+}
+
+impl Default for RuleSet {
+    /// The rules in `rules/deklassiert.toml`.
+    fn default() -> Self {
+        DEFAULT_RULE_SET.clone()
+    }
+}
+
+impl RuleSet {
+    pub fn from_toml(input: &str) -> Result<Self, OtdError> {
+        toml::from_str(input).map_err(|e| OtdError::Config(e.to_string()))
+    }
+
+    /// Read the rules from a TOML file in the format of
+    /// `rules/deklassiert.toml`.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, OtdError> {
+        let path = path.as_ref();
+        let input = std::fs::read_to_string(path)
+            .map_err(|e| OtdError::Config(format!("{}: {}", path.display(), e)))?;
+        toml::from_str(&input).map_err(|e| OtdError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// The built-in rules, without cloning them.
+    pub fn builtin() -> &'static RuleSet {
+        &DEFAULT_RULE_SET
+    }
+
+    /// Run the rules against `vehicle`, using its `vehicle_identifier` and the
+    /// trolley status from the full formation. `None` if nothing fired.
+    pub fn evaluate(
+        &self,
+        vehicle: &Vehicle,
+        trolley_status: Option<&TrolleyStatus>,
+    ) -> Option<Verdict> {
+        let mut fired: Vec<&Rule> = Vec::new();
+        for rule in self.rules.iter() {
+            if !rule.when.matches(vehicle, trolley_status)
+                || rule
+                    .unless
+                    .iter()
+                    .any(|c| c.matches(vehicle, trolley_status))
+            {
+                continue;
+            }
+            match rule.action {
+                Action::Flag => fired.push(rule),
+                Action::Exempt => return None,
+            }
+        }
+
+        let confidence = fired.iter().map(|rule| rule.confidence).max()?;
+        Some(Verdict {
+            rules: fired.iter().map(|rule| rule.name.clone()).collect(),
+            reasons: fired
+                .iter()
+                .map(|rule| {
+                    if rule.description.is_empty() {
+                        rule.name.clone()
+                    } else {
+                        rule.description.clone()
+                    }
+                })
+                .collect(),
+            confidence,
+        })
+    }
+}
+
+impl Condition {
+    fn matches(&self, vehicle: &Vehicle, trolley_status: Option<&TrolleyStatus>) -> bool {
+        if !self.trolley_status.is_empty()
+            && !trolley_status.is_some_and(|status| self.trolley_status.contains(status))
+        {
+            return false;
+        }
+        if !self.slot.is_empty()
+            && !self
+                .slot
+                .iter()
+                .any(|code| parse_vehicle_type(code) == vehicle.vehicle_type)
+        {
+            return false;
+        }
+        let identifier = vehicle.vehicle_identifier.as_ref();
+        if let Some(prefix) = self.type_code_name_prefix.as_ref()
+            && !identifier
+                .and_then(|id| id.type_code_name.as_ref())
+                .is_some_and(|name| name.starts_with(prefix.as_str()))
+        {
+            return false;
+        }
+        if let Some(has_evn) = self.has_evn
+            && identifier.and_then(|id| id.evn.as_ref()).is_some() != has_evn
+        {
+            return false;
+        }
+        if let Some(prefix) = self.evn_prefix.as_ref()
+            && !identifier
+                .and_then(|id| id.evn.as_ref())
+                .is_some_and(|evn| evn.starts_with(prefix.as_str()))
+        {
+            return false;
+        }
+        if !self.evn_interoperability.is_empty() || !self.evn_country.is_empty() {
+            let (interoperability, country) = identifier.map(evn_codes).unwrap_or_default();
+            if !self.evn_interoperability.is_empty()
+                && !interoperability.is_some_and(|code| self.evn_interoperability.contains(&code))
+            {
+                return false;
+            }
+            if !self.evn_country.is_empty()
+                && !country.is_some_and(|code| self.evn_country.contains(&code))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Interoperability and country code of the vehicle's EVN. Falls back to its
+/// leading digits when the EVN is short or its check digit is wrong, so an
+/// exception still applies to a sloppily reported number.
+fn evn_codes(identifier: &VehicleIdentifier) -> (Option<u8>, Option<u8>) {
+    if let Some(Ok(evn)) = identifier.parsed_evn() {
+        return (Some(evn.interoperability), Some(evn.country));
+    }
+    let digits: Vec<u8> = identifier
+        .evn
+        .as_deref()
+        .unwrap_or_default()
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .map(|d| d as u8)
+        .collect();
+    let code = |i: usize| Some(digits.get(i)? * 10 + digits.get(i + 1)?);
+    (code(0), code(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_short_string;
+
+    fn vehicle(slot: &str, type_code_name: &str, evn: &str) -> Vehicle {
+        let mut vehicle = parse_short_string(slot).unwrap().to_vehicles().remove(0);
+        vehicle.vehicle_identifier = Some(VehicleIdentifier {
+            type_code: None,
+            type_code_name: Some(type_code_name.to_string()),
+            build_type_code: None,
+            country_code: None,
+            vehicle_number: None,
+            check_number: None,
+            evn: Some(evn.to_string()),
+            parent_evn: None,
+            position: None,
+        });
+        vehicle
+    }
+
+    #[test]
+    fn trolley_status_fires() {
+        let verdict = RuleSet::default()
+            .evaluate(
+                &vehicle("2:5", "B", "508520739212"),
+                Some(&TrolleyStatus::Deklassiert),
+            )
+            .unwrap();
+        assert_eq!(verdict.rules, vec!["trolley-status"]);
+        assert_eq!(verdict.confidence, Confidence::High);
+    }
+
+    #[test]
+    fn first_class_coach_in_second_class_slot() {
+        let rules = RuleSet::default();
+        let verdict = rules
+            .evaluate(&vehicle("2:5", "A", "50 85 19-94 012-8"), None)
+            .unwrap();
+        assert_eq!(
+            verdict.rules,
+            vec!["first-class-coach-in-second-class-slot"]
+        );
+        assert_eq!(verdict.confidence, Confidence::Medium);
+        assert!(
+            rules
+                .evaluate(&vehicle("1:5", "A", "50 85 19-94 012-8"), None)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn astoro_exception_without_valid_evn() {
+        let rules = RuleSet::default();
+        for evn in ["938500001234", "93 85 0 500 012", "93850500012"] {
+            assert!(
+                rules.evaluate(&vehicle("2:5", "A", evn), None).is_none(),
+                "{evn}"
+            );
+        }
+    }
+
+    #[test]
+    fn first_class_coach_needs_an_evn() {
+        let rules = RuleSet::default();
+        let mut coach = vehicle("2:5", "A", "");
+        coach.vehicle_identifier.as_mut().unwrap().evn = None;
+        assert!(rules.evaluate(&coach, None).is_none());
+        assert!(
+            rules
+                .evaluate(&coach, Some(&TrolleyStatus::Deklassiert))
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn rules_from_path() {
+        let rules = RuleSet::from_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/rules/deklassiert.toml"
+        ))
+        .unwrap();
+        assert_eq!(&rules, RuleSet::builtin());
+        assert!(matches!(
+            RuleSet::from_path("missing.toml"),
+            Err(OtdError::Config(message)) if message.starts_with("missing.toml: ")
+        ));
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+
+use crate::{FormationResponse, TrainId};
+
+/// Shortest time between two fetches of a train.
+const MIN_INTERVAL: TimeDelta = TimeDelta::minutes(5);
+/// Longest time between two fetches of a train that has not finished.
+const MAX_INTERVAL: TimeDelta = TimeDelta::hours(3);
+/// Between fetches of a running train.
+const RUNNING_INTERVAL: TimeDelta = TimeDelta::minutes(10);
+/// After a failed fetch.
+const RETRY_INTERVAL: TimeDelta = TimeDelta::minutes(30);
+/// Fetches without a new `lastUpdate` before a train is refreshed less often.
+const STABLE_AFTER: u32 = 3;
+
+impl FormationResponse {
+    /// Expected departure at the first stop that has one.
+    pub fn first_departure(&self) -> Option<DateTime<FixedOffset>> {
+        self.formations_at_scheduled_stops
+            .iter()
+            .find_map(|stop| stop.scheduled_stop.stop_time.expected_departure())
+    }
+
+    /// Expected time at the last stop that has one, departure first.
+    pub fn last_time(&self) -> Option<DateTime<FixedOffset>> {
+        self.formations_at_scheduled_stops
+            .iter()
+            .rev()
+            .find_map(|stop| {
+                let time = &stop.scheduled_stop.stop_time;
+                time.expected_departure().or(time.expected_arrival())
+            })
+    }
+
+    /// Whether the train has left its last stop. `false` without times.
+    pub fn is_finished(&self, now: DateTime<Utc>) -> bool {
+        self.last_time().is_some_and(|time| time < now)
+    }
+}
+
+/// How long to wait before fetching `formation` again; `None` once the
+/// train has finished.
+///
+/// The interval shrinks towards departure, stays short while the train is
+/// running and halves after a fetch that brought a new `lastUpdate`.
+/// Formations that did not change for [`STABLE_AFTER`] fetches are refreshed
+/// half as often.
+pub fn refresh_interval(
+    formation: &FormationResponse,
+    now: DateTime<Utc>,
+    unchanged: u32,
+) -> Option<TimeDelta> {
+    if formation.is_finished(now) {
+        return None;
+    }
+    let interval = match formation.first_departure() {
+        Some(departure) if departure.with_timezone(&Utc) > now => {
+            (departure.with_timezone(&Utc) - now) / 4
+        }
+        _ => RUNNING_INTERVAL,
+    };
+    let interval = match unchanged {
+        0 => interval / 2,
+        n if n >= STABLE_AFTER => interval * 2,
+        _ => interval,
+    };
+    Some(interval.clamp(MIN_INTERVAL, MAX_INTERVAL))
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    /// `None` once the train has finished.
+    next: Option<DateTime<Utc>>,
+    last_update: Option<DateTime<FixedOffset>>,
+    /// Fetches in a row without a new `lastUpdate`.
+    unchanged: u32,
+}
+
+/// When to fetch each train's formation next, so the API budget goes to
+/// trains about to depart or running.
+#[derive(Debug, Clone, Default)]
+pub struct RefreshScheduler {
+    trains: BTreeMap<TrainId, Entry>,
+}
+
+impl RefreshScheduler {
+    pub fn new() -> Self {
+        RefreshScheduler::default()
+    }
+
+    /// Schedule `train` for now, unless it is already known.
+    pub fn add(&mut self, train: TrainId, now: DateTime<Utc>) {
+        self.trains.entry(train).or_insert(Entry {
+            next: Some(now),
+            last_update: None,
+            unchanged: 0,
+        });
+    }
+
+    /// Record a fetched formation and schedule the next fetch. Returns its
+    /// time, `None` if the train has finished.
+    pub fn fetched(
+        &mut self,
+        formation: &FormationResponse,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let entry = self.trains.entry(formation.train_id()).or_insert(Entry {
+            next: None,
+            last_update: None,
+            unchanged: 0,
+        });
+        entry.unchanged = match entry.last_update {
+            // The first fetch says nothing about how often it changes.
+            None => 1,
+            Some(last_update) if last_update == formation.last_update => entry.unchanged + 1,
+            Some(_) => 0,
+        };
+        entry.last_update = Some(formation.last_update);
+        entry.next =
+            refresh_interval(formation, now, entry.unchanged).map(|interval| now + interval);
+        entry.next
+    }
+
+    /// Record a failed fetch; the train is retried later.
+    pub fn failed(&mut self, train: &TrainId, now: DateTime<Utc>) {
+        if let Some(entry) = self.trains.get_mut(train) {
+            entry.next = Some(now + RETRY_INTERVAL);
+        }
+    }
+
+    pub fn remove(&mut self, train: &TrainId) {
+        self.trains.remove(train);
+    }
+
+    /// Trains to fetch at `now`, longest overdue first.
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<TrainId> {
+        let mut due: Vec<(DateTime<Utc>, &TrainId)> = self
+            .trains
+            .iter()
+            .filter_map(|(train, entry)| Some((entry.next?, train)))
+            .filter(|(next, _)| *next <= now)
+            .collect();
+        due.sort();
+        due.into_iter().map(|(_, train)| train.clone()).collect()
+    }
+
+    /// Time of the next fetch of any train.
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        self.trains.values().filter_map(|entry| entry.next).min()
+    }
+
+    pub fn next_refresh(&self, train: &TrainId) -> Option<DateTime<Utc>> {
+        self.trains.get(train)?.next
+    }
+
+    pub fn len(&self) -> usize {
+        self.trains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trains.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Operator, parse_formation_json};
+
+    /// Brig 14:45 to Romanshorn 18:12 on 2026-01-31.
+    fn formation() -> FormationResponse {
+        parse_formation_json(include_str!("../test_data/test_response.json")).unwrap()
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().to_utc()
+    }
+
+    #[test]
+    fn interval_shrinks_towards_departure() {
+        let formation = formation();
+        let interval = |now| refresh_interval(&formation, at(now), 1);
+        // A quarter of the time left, at most three hours.
+        assert_eq!(interval("2026-01-30T20:00:00+01:00"), Some(MAX_INTERVAL));
+        assert_eq!(
+            interval("2026-01-31T10:45:00+01:00"),
+            Some(TimeDelta::hours(1))
+        );
+        assert_eq!(interval("2026-01-31T14:25:00+01:00"), Some(MIN_INTERVAL));
+    }
+
+    #[test]
+    fn running_and_finished() {
+        let formation = formation();
+        assert_eq!(
+            refresh_interval(&formation, at("2026-01-31T16:00:00+01:00"), 1),
+            Some(RUNNING_INTERVAL)
+        );
+        assert!(!formation.is_finished(at("2026-01-31T18:12:00+01:00")));
+        assert!(formation.is_finished(at("2026-01-31T18:13:00+01:00")));
+        assert_eq!(
+            refresh_interval(&formation, at("2026-01-31T18:13:00+01:00"), 1),
+            None
+        );
+
+        let mut scheduler = RefreshScheduler::new();
+        let now = at("2026-01-31T18:13:00+01:00");
+        assert_eq!(scheduler.fetched(&formation, now), None);
+        assert_eq!(scheduler.len(), 1);
+        assert!(scheduler.due(now + MAX_INTERVAL).is_empty());
+        assert_eq!(scheduler.next_due(), None);
+    }
+
+    #[test]
+    fn changes_speed_up_and_unchanged_fetches_slow_down() {
+        let mut formation = formation();
+        let now = at("2026-01-31T10:45:00+01:00");
+        let mut scheduler = RefreshScheduler::new();
+        // The first fetch, then unchanged (304) ones.
+        let intervals: Vec<TimeDelta> = (0..4)
+            .map(|_| scheduler.fetched(&formation, now).unwrap() - now)
+            .collect();
+        assert_eq!(
+            intervals,
+            vec![
+                TimeDelta::hours(1),
+                TimeDelta::hours(1),
+                TimeDelta::hours(2),
+                TimeDelta::hours(2)
+            ]
+        );
+
+        formation.last_update += TimeDelta::minutes(1);
+        assert_eq!(
+            scheduler.fetched(&formation, now),
+            Some(now + TimeDelta::minutes(30))
+        );
+    }
+
+    #[test]
+    fn failed_fetch_is_retried() {
+        let formation = formation();
+        let train = formation.train_id();
+        let now = at("2026-01-31T10:45:00+01:00");
+        let mut scheduler = RefreshScheduler::new();
+        scheduler.add(train.clone(), now);
+        assert_eq!(scheduler.due(now), vec![train.clone()]);
+
+        scheduler.failed(&train, now);
+        assert!(scheduler.due(now).is_empty());
+        assert_eq!(scheduler.next_refresh(&train), Some(now + RETRY_INTERVAL));
+        assert_eq!(scheduler.due(now + RETRY_INTERVAL), vec![train.clone()]);
+
+        // Unknown trains are not scheduled by a failure.
+        let unknown = TrainId::new(Operator::Bls, 1);
+        scheduler.failed(&unknown, now);
+        assert_eq!(scheduler.next_refresh(&unknown), None);
+    }
+
+    #[test]
+    fn due_longest_overdue_first() {
+        let now = at("2026-01-31T10:45:00+01:00");
+        let mut scheduler = RefreshScheduler::new();
+        let first = TrainId::new(Operator::Sbb, 825);
+        let second = TrainId::new(Operator::Sbb, 812);
+        let third = TrainId::new(Operator::Bls, 965);
+        scheduler.add(first.clone(), now - TimeDelta::minutes(1));
+        scheduler.add(second.clone(), now);
+        scheduler.add(third.clone(), now - TimeDelta::minutes(5));
+        // Already known, so not moved.
+        scheduler.add(second.clone(), now - TimeDelta::minutes(10));
+        scheduler.add(TrainId::new(Operator::Sbb, 1), now + TimeDelta::minutes(1));
+
+        assert_eq!(scheduler.due(now), vec![third, first, second.clone()]);
+        assert_eq!(scheduler.next_due(), Some(now - TimeDelta::minutes(5)));
+
+        scheduler.remove(&second);
+        assert_eq!(scheduler.len(), 3);
+    }
+}
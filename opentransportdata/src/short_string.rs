@@ -0,0 +1,659 @@
+//! Lossless parser for `formationShortString`.
+//!
+//! The string is a comma separated list of vehicles, `@X` sector markers,
+//! `[...]` traction units and `(...)` passage groups, e.g.
+//! `@A,F,[(LK,1:13,>2:12@B,2:10#VH),(FA:9#VH;NF,1:1#BHP;BZ;NF,LK)],F`.
+//! Every node keeps its byte span, and `Display` writes the tree back to the
+//! exact input.
+
+use std::fmt;
+
+use crate::{Offer, StatusFlag, Vehicle, VehicleType, parse_offer, parse_vehicle_type};
+
+/// Byte range into the parsed string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormationString {
+    pub nodes: Vec<Node>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Sector(SectorMarker),
+    Vehicle(VehicleNode),
+    /// `[...]`, vehicles coupled into one traction unit
+    Unit(TractionUnit),
+    /// `(...)`, vehicles with passage between them but not to the outside
+    Group(PassageGroup),
+}
+
+/// `@X`, all following vehicles are in sector `X`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectorMarker {
+    pub sector: char,
+    /// Written directly after the previous element (`2:8@B`) instead of after a comma.
+    pub attached: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TractionUnit {
+    pub nodes: Vec<Node>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassageGroup {
+    pub nodes: Vec<Node>,
+    pub open: Paren,
+    pub close: Paren,
+    pub span: Span,
+}
+
+/// Where a parenthesis sits relative to the vehicle token it touches.
+/// SBB writes `-(LK` and `FA):9`, so they are not always on a token boundary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Paren {
+    /// Before the first or after the last token of the group.
+    Outside,
+    /// `-(LK`
+    AfterStatus,
+    /// `FA):9#VH`
+    AfterType,
+    /// `FA:9)#VH`
+    AfterNumber,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VehicleNode {
+    pub status: Vec<StatusFlag>,
+    pub type_code: Token,
+    pub number: Option<Token>,
+    pub offers: Vec<Token>,
+    pub span: Span,
+}
+
+impl VehicleNode {
+    pub fn vehicle_type(&self) -> VehicleType {
+        parse_vehicle_type(&self.type_code.text)
+    }
+
+    pub fn order_number(&self) -> Option<u32> {
+        self.number.as_ref().and_then(|n| n.text.parse().ok())
+    }
+
+    pub fn offers(&self) -> Vec<Offer> {
+        self.offers.iter().map(|o| parse_offer(&o.text)).collect()
+    }
+
+    fn write_with(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        open: Option<Paren>,
+        close: Option<Paren>,
+    ) -> fmt::Result {
+        if open == Some(Paren::Outside) {
+            f.write_str("(")?;
+        }
+        for flag in self.status.iter() {
+            if let Some(symbol) = status_symbol(flag) {
+                write!(f, "{}", symbol)?;
+            }
+        }
+        if open == Some(Paren::AfterStatus) {
+            f.write_str("(")?;
+        }
+        f.write_str(&self.type_code.text)?;
+        if close == Some(Paren::AfterType) {
+            f.write_str(")")?;
+        }
+        if let Some(number) = self.number.as_ref() {
+            write!(f, ":{}", number.text)?;
+        }
+        if close == Some(Paren::AfterNumber) {
+            f.write_str(")")?;
+        }
+        if !self.offers.is_empty() {
+            f.write_str("#")?;
+            for (i, offer) in self.offers.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(";")?;
+                }
+                f.write_str(&offer.text)?;
+            }
+        }
+        if close == Some(Paren::Outside) {
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShortStringError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ShortStringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for ShortStringError {}
+
+pub(crate) fn status_flag(symbol: char) -> Option<StatusFlag> {
+    match symbol {
+        '-' => Some(StatusFlag::Closed),
+        '>' => Some(StatusFlag::GroupBoarding),
+        '=' => Some(StatusFlag::Reserved),
+        '%' => Some(StatusFlag::OpenUnserved),
+        _ => None,
+    }
+}
+
+fn status_symbol(flag: &StatusFlag) -> Option<char> {
+    match flag {
+        StatusFlag::Closed => Some('-'),
+        StatusFlag::GroupBoarding => Some('>'),
+        StatusFlag::Reserved => Some('='),
+        StatusFlag::OpenUnserved => Some('%'),
+        StatusFlag::Deklassiert => None,
+    }
+}
+
+pub fn parse_short_string(input: &str) -> Result<FormationString, ShortStringError> {
+    let mut parser = Parser {
+        src: input,
+        pos: 0,
+        closed_by_vehicle: None,
+    };
+    if input.is_empty() {
+        return Ok(FormationString {
+            nodes: Vec::new(),
+            span: Span::new(0, 0),
+        });
+    }
+    let nodes = parser.parse_list(Context::Top, None)?;
+    if let Some(ch) = parser.peek() {
+        return Err(parser.error(format!("unexpected `{}`", ch)));
+    }
+    Ok(FormationString {
+        nodes,
+        span: Span::new(0, input.len()),
+    })
+}
+
+impl fmt::Display for FormationString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_nodes(f, &self.nodes, None, None)
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Sector(marker) => write!(f, "@{}", marker.sector),
+            Node::Vehicle(vehicle) => vehicle.write_with(f, None, None),
+            Node::Unit(unit) => {
+                f.write_str("[")?;
+                write_nodes(f, &unit.nodes, None, None)?;
+                f.write_str("]")
+            }
+            Node::Group(group) => {
+                let first_is_vehicle = matches!(group.nodes.first(), Some(Node::Vehicle(_)));
+                let last_is_vehicle = matches!(group.nodes.last(), Some(Node::Vehicle(_)));
+                if !first_is_vehicle {
+                    f.write_str("(")?;
+                }
+                write_nodes(
+                    f,
+                    &group.nodes,
+                    first_is_vehicle.then_some(group.open),
+                    last_is_vehicle.then_some(group.close),
+                )?;
+                if !last_is_vehicle {
+                    f.write_str(")")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Write a node list; `open`/`close` are the group parentheses carried by the
+/// first and last vehicle.
+fn write_nodes(
+    f: &mut fmt::Formatter<'_>,
+    nodes: &[Node],
+    open: Option<Paren>,
+    close: Option<Paren>,
+) -> fmt::Result {
+    let last = nodes.len().saturating_sub(1);
+    for (i, node) in nodes.iter().enumerate() {
+        let attached = matches!(node, Node::Sector(marker) if marker.attached);
+        if i > 0 && !attached {
+            f.write_str(",")?;
+        }
+        match node {
+            Node::Vehicle(vehicle) => vehicle.write_with(
+                f,
+                if i == 0 { open } else { None },
+                if i == last { close } else { None },
+            )?,
+            other => write!(f, "{}", other)?,
+        }
+    }
+    Ok(())
+}
+
+impl FormationString {
+    /// Vehicles in order, with sector, passage and traction unit resolved.
+    pub fn to_vehicles(&self) -> Vec<Vehicle> {
+        let mut flattener = Flattener::default();
+        flattener.visit(&self.nodes, None);
+        flattener.vehicles
+    }
+
+    pub fn units(&self) -> impl Iterator<Item = &TractionUnit> {
+        self.nodes.iter().filter_map(|node| match node {
+            Node::Unit(unit) => Some(unit),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Flattener {
+    vehicles: Vec<Vehicle>,
+    sector: Option<char>,
+    units: usize,
+}
+
+impl Flattener {
+    fn visit(&mut self, nodes: &[Node], unit: Option<usize>) {
+        for node in nodes {
+            match node {
+                Node::Sector(marker) => self.sector = Some(marker.sector),
+                Node::Vehicle(vehicle) => self.push(vehicle, unit),
+                Node::Unit(inner) => {
+                    let index = self.units;
+                    self.units += 1;
+                    self.visit(&inner.nodes, Some(index));
+                }
+                Node::Group(group) => {
+                    let first = self.vehicles.len();
+                    self.visit(&group.nodes, unit);
+                    if let Some(vehicle) = self.vehicles.get_mut(first) {
+                        vehicle.no_passage_left = true;
+                    }
+                    if self.vehicles.len() > first
+                        && let Some(vehicle) = self.vehicles.last_mut()
+                    {
+                        vehicle.no_passage_right = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, vehicle: &VehicleNode, unit: Option<usize>) {
+        self.vehicles.push(Vehicle {
+            sector: self.sector,
+            status: vehicle.status.clone(),
+            no_passage_left: false,
+            no_passage_right: false,
+            vehicle_type: vehicle.vehicle_type(),
+            order_number: vehicle.order_number(),
+            offers: vehicle.offers(),
+            vehicle_identifier: None,
+            traction_unit: unit,
+            destination: None,
+            deklassiert: None,
+        });
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Context {
+    Top,
+    Unit,
+    Group,
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    /// Set when the last vehicle token contained the `)` ending its group.
+    closed_by_vehicle: Option<Paren>,
+}
+
+/// Characters that end a type code, order number or offer.
+const DELIMITERS: &[char] = &[',', '[', ']', '(', ')', '@', ':', '#', ';'];
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, ch: char) -> bool {
+        if self.peek() == Some(ch) {
+            self.pos += ch.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ShortStringError {
+        ShortStringError {
+            offset: self.pos,
+            message: message.into(),
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<Token, ShortStringError> {
+        let start = self.pos;
+        while let Some(ch) = self.peek() {
+            if DELIMITERS.contains(&ch) {
+                break;
+            }
+            self.pos += ch.len_utf8();
+        }
+        if self.pos == start {
+            return Err(self.error(format!("expected {}", what)));
+        }
+        Ok(Token {
+            text: self.src[start..self.pos].to_string(),
+            span: Span::new(start, self.pos),
+        })
+    }
+
+    fn sector(&mut self, attached: bool) -> Result<SectorMarker, ShortStringError> {
+        let start = self.pos;
+        self.eat('@');
+        let sector = self
+            .peek()
+            .filter(|ch| !DELIMITERS.contains(ch))
+            .ok_or_else(|| self.error("expected sector letter"))?;
+        self.pos += sector.len_utf8();
+        Ok(SectorMarker {
+            sector,
+            attached,
+            span: Span::new(start, self.pos),
+        })
+    }
+
+    /// Parse list entries up to the end of `context`. `pending` carries the
+    /// status flags already read in front of a group's `(`.
+    fn parse_list(
+        &mut self,
+        context: Context,
+        mut pending: Option<(Vec<StatusFlag>, usize)>,
+    ) -> Result<Vec<Node>, ShortStringError> {
+        let mut nodes = Vec::new();
+        loop {
+            if let Some((status, start)) = pending.take() {
+                let (vehicle, close) = self.vehicle(status, start, context)?;
+                nodes.push(Node::Vehicle(vehicle));
+                if close.is_some() {
+                    return Ok(nodes);
+                }
+            } else if self.peek() == Some('@') {
+                nodes.push(Node::Sector(self.sector(false)?));
+            } else if self.peek() == Some('[') {
+                if context != Context::Top {
+                    return Err(self.error("nested traction unit"));
+                }
+                let start = self.pos;
+                self.eat('[');
+                let inner = self.parse_list(Context::Unit, None)?;
+                if !self.eat(']') {
+                    return Err(self.error("expected `]`"));
+                }
+                nodes.push(Node::Unit(TractionUnit {
+                    nodes: inner,
+                    span: Span::new(start, self.pos),
+                }));
+            } else {
+                let start = self.pos;
+                let mut status = Vec::new();
+                while let Some(flag) = self.peek().and_then(status_flag) {
+                    status.push(flag);
+                    self.pos += 1;
+                }
+                if self.peek() == Some('(') {
+                    if context == Context::Group {
+                        return Err(self.error("nested passage group"));
+                    }
+                    let open = if status.is_empty() {
+                        Paren::Outside
+                    } else {
+                        Paren::AfterStatus
+                    };
+                    self.eat('(');
+                    let pending = (!status.is_empty()).then_some((status, start));
+                    let inner = self.parse_list(Context::Group, pending)?;
+                    let close = self.take_close(&inner)?;
+                    nodes.push(Node::Group(PassageGroup {
+                        nodes: inner,
+                        open,
+                        close,
+                        span: Span::new(start, self.pos),
+                    }));
+                } else {
+                    let (vehicle, close) = self.vehicle(status, start, context)?;
+                    nodes.push(Node::Vehicle(vehicle));
+                    if close.is_some() {
+                        return Ok(nodes);
+                    }
+                }
+            }
+
+            if self.peek() == Some('@') {
+                nodes.push(Node::Sector(self.sector(true)?));
+            }
+
+            match self.peek() {
+                Some(',') => {
+                    self.pos += 1;
+                }
+                Some(')') if context == Context::Group => return Ok(nodes),
+                Some(']') if context == Context::Unit => return Ok(nodes),
+                None if context == Context::Top => return Ok(nodes),
+                Some(ch) => return Err(self.error(format!("unexpected `{}`", ch))),
+                None => return Err(self.error("unexpected end of string")),
+            }
+        }
+    }
+
+    /// Read the `)` ending a group, unless the last vehicle already consumed it.
+    fn take_close(&mut self, nodes: &[Node]) -> Result<Paren, ShortStringError> {
+        if let Some(close) = self.closed_by_vehicle.take() {
+            return Ok(close);
+        }
+        if nodes.is_empty() || !self.eat(')') {
+            return Err(self.error("expected `)`"));
+        }
+        Ok(Paren::Outside)
+    }
+
+    fn vehicle(
+        &mut self,
+        status: Vec<StatusFlag>,
+        start: usize,
+        context: Context,
+    ) -> Result<(VehicleNode, Option<Paren>), ShortStringError> {
+        let in_group = context == Context::Group;
+        let mut close = None;
+
+        let type_code = self.word("vehicle type")?;
+        if in_group && self.eat(')') {
+            close = Some(Paren::AfterType);
+        }
+        let number = if self.eat(':') {
+            Some(self.word("order number")?)
+        } else {
+            None
+        };
+        if in_group && close.is_none() && self.eat(')') {
+            close = Some(Paren::AfterNumber);
+        }
+        let mut offers = Vec::new();
+        if self.eat('#') {
+            loop {
+                offers.push(self.word("offer")?);
+                if !self.eat(';') {
+                    break;
+                }
+            }
+        }
+        let end = self.pos;
+        if in_group && close.is_none() && self.eat(')') {
+            close = Some(Paren::Outside);
+        }
+
+        // A `)` that ends up at the end of the token is written after it.
+        close = match close {
+            Some(Paren::AfterType) if number.is_none() && offers.is_empty() => Some(Paren::Outside),
+            Some(Paren::AfterNumber) if offers.is_empty() => Some(Paren::Outside),
+            other => other,
+        };
+        self.closed_by_vehicle = close;
+
+        let span_end = if close == Some(Paren::Outside) {
+            end
+        } else {
+            self.pos
+        };
+        Ok((
+            VehicleNode {
+                status,
+                type_code,
+                number,
+                offers,
+                span: Span::new(start, span_end),
+            },
+            close,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_formation_json;
+
+    fn round_trip(input: &str) -> FormationString {
+        let parsed = parse_short_string(input).unwrap();
+        assert_eq!(parsed.to_string(), input);
+        parsed
+    }
+
+    #[test]
+    fn fixture_strings_round_trip() {
+        let formation =
+            parse_formation_json(include_str!("../test_data/test_response.json")).unwrap();
+        for stop in formation.formations_at_scheduled_stops.iter() {
+            round_trip(&stop.formation_short.formation_short_string);
+        }
+    }
+
+    #[test]
+    fn empty_string() {
+        let parsed = round_trip("");
+        assert!(parsed.nodes.is_empty());
+        assert!(parsed.to_vehicles().is_empty());
+    }
+
+    #[test]
+    fn group_in_unit() {
+        let parsed = round_trip("@A,F,[(LK,1:1#BHP;BZ;NF@B,2:2,FA):9#VH]");
+        assert_eq!(parsed.units().count(), 1);
+        let Node::Group(group) = &parsed.units().next().unwrap().nodes[0] else {
+            panic!("expected a passage group");
+        };
+        assert_eq!(group.open, Paren::Outside);
+        assert_eq!(group.close, Paren::AfterType);
+
+        let vehicles = parsed.to_vehicles();
+        assert_eq!(vehicles.len(), 5);
+        assert_eq!(vehicles[0].traction_unit, None);
+        assert!(vehicles[1..].iter().all(|v| v.traction_unit == Some(0)));
+        assert!(vehicles[1].no_passage_left && !vehicles[1].no_passage_right);
+        assert!(vehicles[4].no_passage_right);
+        assert_eq!(vehicles[3].sector, Some('B'));
+        assert_eq!(vehicles[4].vehicle_type, VehicleType::FamilyCar);
+        assert_eq!(vehicles[4].order_number, Some(9));
+    }
+
+    #[test]
+    fn parentheses_inside_tokens() {
+        for input in ["-(LK,-1,-2)", "(2:1,FA):9", "(2:1,FA:9)#VH", "%(2:1,2:2)"] {
+            round_trip(input);
+        }
+    }
+
+    #[test]
+    fn status_markers_and_offers() {
+        let parsed = round_trip("-2:1,%1:2#BZ,>=2:3#VH;NF;XY,-%W2:4");
+        let vehicles = parsed.to_vehicles();
+        assert_eq!(vehicles[0].status, vec![StatusFlag::Closed]);
+        assert_eq!(vehicles[1].status, vec![StatusFlag::OpenUnserved]);
+        assert_eq!(vehicles[1].offers, vec![Offer::BusinessZone]);
+        assert_eq!(
+            vehicles[2].status,
+            vec![StatusFlag::GroupBoarding, StatusFlag::Reserved]
+        );
+        assert_eq!(
+            vehicles[2].offers,
+            vec![
+                Offer::BikeHooks,
+                Offer::LowFloor,
+                Offer::Unknown("XY".to_string())
+            ]
+        );
+        assert_eq!(
+            vehicles[3].status,
+            vec![StatusFlag::Closed, StatusFlag::OpenUnserved]
+        );
+        assert_eq!(vehicles[3].vehicle_type, VehicleType::DiningSecondClass);
+    }
+
+    #[test]
+    fn spans() {
+        let parsed = round_trip("@A,2:5#NF,[LK]");
+        let Node::Vehicle(vehicle) = &parsed.nodes[1] else {
+            panic!("expected a vehicle");
+        };
+        assert_eq!(vehicle.span, Span::new(3, 9));
+        assert_eq!(vehicle.number.as_ref().unwrap().span, Span::new(5, 6));
+        assert_eq!(parsed.units().next().unwrap().span, Span::new(10, 14));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        for input in ["[[LK]]", "((2:1))", "2:1,", "(2:1", "[LK", "2:1#", "@"] {
+            assert!(parse_short_string(input).is_err(), "{input:?}");
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stop_event::{XmlText, parse_delivery};
+use crate::{HubStop, OtdError};
+
+/// Searches remembered by a [`StationCache`] before it starts over.
+const MAX_SEARCHES: usize = 256;
+
+/// WGS84 coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoPosition {
+    pub longitude: f64,
+    pub latitude: f64,
+}
+
+/// A stop place found by an OJP `LocationInformationRequest`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Station {
+    pub uic: u32,
+    /// E.g. `Bern`, without the locality OJP appends to the place name.
+    pub name: String,
+    pub position: Option<GeoPosition>,
+}
+
+impl From<Station> for HubStop {
+    fn from(station: Station) -> Self {
+        HubStop::new(station.uic, station.name)
+    }
+}
+
+/// Stations and name searches, shared by every clone. Stations do not
+/// change during a run, so nothing expires.
+#[derive(Debug, Clone, Default)]
+pub struct StationCache {
+    inner: Arc<Mutex<Stations>>,
+}
+
+#[derive(Debug, Default)]
+struct Stations {
+    by_uic: HashMap<u32, Station>,
+    /// Normalized search text to UIC numbers, in result order.
+    searches: HashMap<String, Vec<u32>>,
+}
+
+impl StationCache {
+    pub fn new() -> Self {
+        StationCache::default()
+    }
+
+    pub fn get(&self, uic: u32) -> Option<Station> {
+        self.inner.lock().unwrap().by_uic.get(&uic).cloned()
+    }
+
+    /// Results of an earlier [`StationCache::insert_search`] for `name`.
+    pub fn search(&self, name: &str) -> Option<Vec<Station>> {
+        let stations = self.inner.lock().unwrap();
+        let uics = stations.searches.get(&normalize(name))?;
+        Some(
+            uics.iter()
+                .filter_map(|uic| stations.by_uic.get(uic).cloned())
+                .collect(),
+        )
+    }
+
+    pub fn insert(&self, station: Station) {
+        self.inner
+            .lock()
+            .unwrap()
+            .by_uic
+            .insert(station.uic, station);
+    }
+
+    pub fn insert_search(&self, name: &str, results: &[Station]) {
+        let mut stations = self.inner.lock().unwrap();
+        if stations.searches.len() >= MAX_SEARCHES {
+            stations.searches.clear();
+        }
+        stations.searches.insert(
+            normalize(name),
+            results.iter().map(|station| station.uic).collect(),
+        );
+        for station in results {
+            stations.by_uic.insert(station.uic, station.clone());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().by_uic.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Best match for a configured station name: an exact (case-insensitive)
+/// name match, else the first result.
+pub(crate) fn best_match(name: &str, stations: Vec<Station>) -> Option<Station> {
+    let wanted = normalize(name);
+    let exact = stations
+        .iter()
+        .position(|station| normalize(&station.name) == wanted);
+    stations.into_iter().nth(exact.unwrap_or(0))
+}
+
+/// Stop places in an OJP `LocationInformationRequest` response, in result
+/// order. Places without a numeric stop place reference are skipped.
+pub fn parse_stations(xml: &str) -> Result<Vec<Station>, OtdError> {
+    let results = parse_delivery(xml)?
+        .and_then(|delivery| delivery.location_information_delivery)
+        .map(|delivery| delivery.results)
+        .unwrap_or_default();
+    Ok(results
+        .into_iter()
+        .filter_map(|result| {
+            let place = result.place;
+            let stop_place = place.stop_place?;
+            Some(Station {
+                uic: stop_place.stop_place_ref.parse().ok()?,
+                name: stop_place
+                    .stop_place_name
+                    .or(place.name)
+                    .map(|name| name.text)
+                    .unwrap_or_default(),
+                position: place.geo_position.map(|position| GeoPosition {
+                    longitude: position.longitude,
+                    latitude: position.latitude,
+                }),
+            })
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct XmlLocationInformationDelivery {
+    #[serde(rename = "PlaceResult", default)]
+    results: Vec<XmlPlaceResult>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XmlPlaceResult {
+    place: XmlPlace,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XmlPlace {
+    stop_place: Option<XmlStopPlace>,
+    name: Option<XmlText>,
+    geo_position: Option<XmlGeoPosition>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XmlStopPlace {
+    stop_place_ref: String,
+    stop_place_name: Option<XmlText>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XmlGeoPosition {
+    longitude: f64,
+    latitude: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = include_str!("../test_data/location_information_response.xml");
+
+    fn station(uic: u32, name: &str) -> Station {
+        Station {
+            uic,
+            name: name.to_string(),
+            position: None,
+        }
+    }
+
+    fn uics(stations: &[Station]) -> Vec<u32> {
+        stations.iter().map(|station| station.uic).collect()
+    }
+
+    #[test]
+    fn fields_of_the_fixture() {
+        let stations = parse_stations(RESPONSE).unwrap();
+        assert_eq!(uics(&stations), vec![8507000, 8516161, 8507002]);
+        assert_eq!(stations[0].name, "Bern");
+        assert_eq!(stations[2].name, "Bern Bümpliz Nord");
+        assert_eq!(
+            stations[0].position,
+            Some(GeoPosition {
+                longitude: 7.43913,
+                latitude: 46.94883,
+            })
+        );
+        assert_eq!(
+            HubStop::from(stations[1].clone()),
+            HubStop::new(8516161, "Bern Wankdorf")
+        );
+    }
+
+    #[test]
+    fn empty_response() {
+        let start = RESPONSE.find("<PlaceResult>").unwrap();
+        let end = RESPONSE.rfind("</PlaceResult>").unwrap() + "</PlaceResult>".len();
+        let empty = format!("{}{}", &RESPONSE[..start], &RESPONSE[end..]);
+        let stations = parse_stations(&empty).unwrap();
+        assert!(stations.is_empty());
+        assert_eq!(best_match("Bern", stations), None);
+    }
+
+    #[test]
+    fn exact_name_before_the_first_result() {
+        let stations = parse_stations(RESPONSE).unwrap();
+        let found = |name: &str| best_match(name, stations.clone()).map(|station| station.uic);
+        assert_eq!(found("Bern"), Some(8507000));
+        assert_eq!(found("  bern   WANKDORF "), Some(8516161));
+        assert_eq!(found("Bern Bümpliz Nord"), Some(8507002));
+        // no exact match: the first result, OJP ranks them
+        assert_eq!(found("Wankdorf"), Some(8507000));
+        assert_eq!(found("Bern (Bern)"), Some(8507000));
+    }
+
+    #[test]
+    fn ambiguous_names() {
+        let stations = vec![
+            station(8500010, "Basel SBB"),
+            station(8509000, "Chur"),
+            station(8509002, "Chur"),
+        ];
+        assert_eq!(best_match("chur", stations.clone()).unwrap().uic, 8509000);
+        assert_eq!(best_match("Basel", stations).unwrap().uic, 8500010);
+    }
+
+    #[test]
+    fn cached_searches() {
+        let cache = StationCache::new();
+        assert!(cache.is_empty());
+        assert_eq!(cache.search("bern"), None);
+
+        let stations = parse_stations(RESPONSE).unwrap();
+        cache.insert_search(" Bern ", &stations);
+        cache.insert_search("Nowhere", &[]);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(uics(&cache.search("BERN").unwrap()), uics(&stations));
+        assert_eq!(cache.search("nowhere"), Some(Vec::new()));
+        assert_eq!(cache.search("Bern Wankdorf"), None);
+        assert_eq!(cache.get(8516161), Some(stations[1].clone()));
+        assert_eq!(cache.get(8503000), None);
+
+        // clones share the cache
+        let shared = cache.clone();
+        shared.insert(station(8503000, "Zürich HB"));
+        assert_eq!(cache.get(8503000).unwrap().name, "Zürich HB");
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn searches_start_over_but_stations_stay() {
+        let cache = StationCache::new();
+        cache.insert_search("bern", &[station(8507000, "Bern")]);
+        for i in 1..MAX_SEARCHES {
+            cache.insert_search(&format!("search {i}"), &[]);
+        }
+        assert!(cache.search("bern").is_some());
+
+        cache.insert_search("chur", &[station(8509000, "Chur")]);
+        assert_eq!(cache.search("bern"), None);
+        assert_eq!(cache.search("search 1"), None);
+        assert_eq!(uics(&cache.search("chur").unwrap()), vec![8509000]);
+        assert_eq!(cache.get(8507000).unwrap().name, "Bern");
+    }
+}
@@ -0,0 +1,576 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeDelta, Utc};
+use quick_xml::events::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::stations::XmlLocationInformationDelivery;
+use crate::trip_info::XmlTripInfoDelivery;
+use crate::{FormationResponse, Operator, OtdError, StopTime, TrainId, strip_namespaces};
+
+/// One departure or arrival from an `OJPStopEventDelivery`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StopEvent {
+    /// `StopEventResult/Id`, unique within one response.
+    pub id: String,
+    pub previous_calls: Vec<CallAtStop>,
+    /// The call at the requested stop.
+    pub this_call: CallAtStop,
+    pub onward_calls: Vec<CallAtStop>,
+    pub service: Service,
+    /// From the service's `OperatorRef`; [`crate::DiscoveryQuery`] fills it
+    /// in from its line catalog for company numbers without a known `evu`
+    /// code.
+    pub operator: Option<Operator>,
+}
+
+/// A stop of the service, with timetabled and estimated times.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallAtStop {
+    /// UIC number as text, e.g. `"8507000"`.
+    pub stop_point_ref: String,
+    pub stop_point_name: String,
+    pub planned_quay: Option<String>,
+    pub estimated_quay: Option<String>,
+    pub arrival: Option<ServiceTime>,
+    pub departure: Option<ServiceTime>,
+    /// Position of the stop in the journey, 1-based.
+    pub order: u32,
+    /// The service passes without stopping, e.g. after a short-notice change.
+    pub not_serviced: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceTime {
+    pub timetabled: DateTime<Utc>,
+    pub estimated: Option<DateTime<Utc>>,
+}
+
+/// The journey a [`StopEvent`] belongs to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Service {
+    pub operating_day: NaiveDate,
+    /// E.g. `ch:1:sjyid:100001:825-001`.
+    pub journey_ref: String,
+    /// Line as shown to passengers, e.g. `IC8`.
+    pub public_code: Option<String>,
+    pub line_ref: Option<String>,
+    pub direction_ref: Option<String>,
+    /// `PtMode`, e.g. `rail`.
+    pub mode: Option<String>,
+    /// E.g. `IC`.
+    pub mode_short_name: Option<String>,
+    pub published_service_name: Option<String>,
+    pub train_number: Option<String>,
+    pub operator_ref: Option<String>,
+    pub cancelled: bool,
+    pub origin_stop_point_ref: Option<String>,
+    pub origin: Option<String>,
+    pub destination_stop_point_ref: Option<String>,
+    pub destination: Option<String>,
+}
+
+impl StopEvent {
+    /// All calls in journey order.
+    pub fn calls(&self) -> impl Iterator<Item = &CallAtStop> {
+        self.previous_calls
+            .iter()
+            .chain(std::iter::once(&self.this_call))
+            .chain(self.onward_calls.iter())
+    }
+
+    pub fn train_number(&self) -> Option<i32> {
+        self.service.train_number.as_ref()?.parse().ok()
+    }
+
+    /// `None` if neither the `OperatorRef` nor the line catalog tells.
+    pub fn operator(&self) -> Option<Operator> {
+        self.operator.clone()
+    }
+
+    pub fn train_id(&self) -> Option<TrainId> {
+        Some(TrainId::new(self.operator()?, self.train_number()?))
+    }
+
+    /// Arrival at the last stop of the response, estimated if known.
+    pub fn last_arrival(&self) -> Option<DateTime<Utc>> {
+        self.calls()
+            .filter_map(|call| call.arrival.map(|time| time.expected()))
+            .max()
+    }
+}
+
+impl FormationResponse {
+    /// Set estimated times on the stops matching `event`'s calls, by UIC
+    /// number and, for stops served twice, the planned time. Returns how many
+    /// stops matched. `event` must be for this train.
+    pub fn apply_realtime(&mut self, event: &StopEvent) -> usize {
+        let mut matched = 0;
+        for call in event.calls() {
+            let Some(uic) = call.uic() else {
+                continue;
+            };
+            let mut candidates = self
+                .formations_at_scheduled_stops
+                .iter_mut()
+                .map(|stop| &mut stop.scheduled_stop)
+                .filter(|stop| stop.stop_point.uic == uic)
+                .collect::<Vec<_>>();
+            let index = candidates
+                .iter()
+                .position(|stop| call.matches_planned(&stop.stop_time))
+                .unwrap_or(0);
+            let Some(stop) = candidates.get_mut(index) else {
+                continue;
+            };
+            let time = &mut stop.stop_time;
+            if let Some(arrival) = call.arrival
+                && let Some(estimated) = estimate(arrival, time.arrival_time)
+            {
+                time.estimated_arrival_time = Some(estimated);
+            }
+            if let Some(departure) = call.departure
+                && let Some(estimated) = estimate(departure, time.departure_time)
+            {
+                time.estimated_departure_time = Some(estimated);
+            }
+            matched += 1;
+        }
+        matched
+    }
+}
+
+impl CallAtStop {
+    pub fn uic(&self) -> Option<u32> {
+        self.stop_point_ref.parse().ok()
+    }
+
+    /// Estimated quay if the train was moved, else the planned one.
+    pub fn quay(&self) -> Option<&str> {
+        self.estimated_quay
+            .as_deref()
+            .or(self.planned_quay.as_deref())
+    }
+
+    pub fn quay_changed(&self) -> bool {
+        self.estimated_quay.is_some() && self.estimated_quay != self.planned_quay
+    }
+
+    fn matches_planned(&self, stop_time: &StopTime) -> bool {
+        let same = |time: Option<ServiceTime>, planned: Option<DateTime<FixedOffset>>| matches!((time, planned), (Some(time), Some(planned)) if time.timetabled == planned);
+        same(self.arrival, stop_time.arrival_time) || same(self.departure, stop_time.departure_time)
+    }
+}
+
+/// The OJP delay applied to the planned time of the formation, so that
+/// small timetable differences between the two APIs do not show up as
+/// delays. `None` without realtime data.
+fn estimate(
+    time: ServiceTime,
+    planned: Option<DateTime<FixedOffset>>,
+) -> Option<DateTime<FixedOffset>> {
+    let delay = time.delay()?;
+    Some(match planned {
+        Some(planned) => planned + delay,
+        None => time.expected().fixed_offset(),
+    })
+}
+
+impl ServiceTime {
+    /// Estimated time if known, else the timetable.
+    pub fn expected(&self) -> DateTime<Utc> {
+        self.estimated.unwrap_or(self.timetabled)
+    }
+
+    /// `None` without realtime data; negative when early.
+    pub fn delay(&self) -> Option<TimeDelta> {
+        self.estimated.map(|estimated| estimated - self.timetabled)
+    }
+}
+
+/// Read the stop events of an OJP `StopEventRequest` response. A response
+/// without an `OJPStopEventDelivery` (e.g. no departures) yields no events.
+/// Results that cannot be read are skipped; [`parse_stop_event_results`]
+/// reports them.
+pub fn parse_stop_events(xml: &str) -> Result<Vec<StopEvent>, OtdError> {
+    Ok(parse_stop_event_results(xml)?
+        .into_iter()
+        .filter_map(Result::ok)
+        .collect())
+}
+
+/// Each `StopEventResult` of an OJP `StopEventRequest` response, read on its
+/// own so that one malformed result does not hide the others.
+pub fn parse_stop_event_results(xml: &str) -> Result<Vec<Result<StopEvent, OtdError>>, OtdError> {
+    let cleaned = strip_namespaces(xml);
+    let document: XmlOjp = from_xml(&cleaned)?;
+    if document
+        .response
+        .and_then(|response| response.delivery.stop_event_delivery)
+        .is_none()
+    {
+        return Ok(Vec::new());
+    }
+    let results = result_fragments(&cleaned)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, (offset, fragment))| {
+            from_xml::<XmlStopEventResult>(fragment)
+                .map(StopEvent::from)
+                .map_err(|e| match e {
+                    OtdError::Xml {
+                        path,
+                        position,
+                        message,
+                    } => OtdError::Xml {
+                        path: match path.as_str() {
+                            "." => format!("StopEventResult[{}]", index),
+                            path => format!("StopEventResult[{}].{}", index, path),
+                        },
+                        position: offset + position,
+                        message,
+                    },
+                    e => e,
+                })
+        })
+        .collect();
+    Ok(results)
+}
+
+/// The `ServiceDelivery` of an OJP response, `None` for a request document.
+pub(crate) fn parse_delivery(xml: &str) -> Result<Option<XmlServiceDelivery>, OtdError> {
+    let document: XmlOjp = from_xml(&strip_namespaces(xml))?;
+    Ok(document.response.map(|response| response.delivery))
+}
+
+/// Deserialize XML without namespace prefixes, keeping the path and position
+/// of the first error.
+fn from_xml<'de, T: Deserialize<'de>>(xml: &'de str) -> Result<T, OtdError> {
+    let mut deserializer = quick_xml::de::Deserializer::from_str(xml);
+    serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+        let reader = deserializer.get_ref().get_ref();
+        // Syntax errors know where the broken markup starts.
+        let position = match reader.error_position() {
+            0 => reader.buffer_position(),
+            position => position,
+        };
+        OtdError::from_xml(e, position)
+    })
+}
+
+/// Byte offset and text of each `StopEventResult` element.
+fn result_fragments(xml: &str) -> Result<Vec<(u64, &str)>, OtdError> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut fragments = Vec::new();
+    loop {
+        let start = reader.buffer_position();
+        let event = reader.read_event().map_err(|e| OtdError::Xml {
+            path: String::new(),
+            position: reader.error_position(),
+            message: e.to_string(),
+        })?;
+        match event {
+            Event::Start(element) if element.name().as_ref() == b"StopEventResult" => {
+                reader
+                    .read_to_end(element.name())
+                    .map_err(|e| OtdError::Xml {
+                        path: String::new(),
+                        position: reader.error_position(),
+                        message: e.to_string(),
+                    })?;
+                let end = reader.buffer_position();
+                fragments.push((start, &xml[start as usize..end as usize]));
+            }
+            Event::Eof => return Ok(fragments),
+            _ => {}
+        }
+    }
+}
+
+// Shapes of the OJP 2.0 XML, after `strip_namespaces`.
+
+#[derive(Deserialize)]
+struct XmlOjp {
+    #[serde(rename = "OJPResponse")]
+    response: Option<XmlOjpResponse>,
+}
+
+#[derive(Deserialize)]
+struct XmlOjpResponse {
+    #[serde(rename = "ServiceDelivery")]
+    delivery: XmlServiceDelivery,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct XmlServiceDelivery {
+    #[serde(rename = "OJPStopEventDelivery")]
+    stop_event_delivery: Option<XmlStopEventDelivery>,
+    #[serde(rename = "OJPTripInfoDelivery")]
+    pub(crate) trip_info_delivery: Option<XmlTripInfoDelivery>,
+    #[serde(rename = "OJPLocationInformationDelivery")]
+    pub(crate) location_information_delivery: Option<XmlLocationInformationDelivery>,
+}
+
+/// The results are read one by one, see [`parse_stop_event_results`].
+#[derive(Deserialize)]
+struct XmlStopEventDelivery {}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XmlStopEventResult {
+    id: String,
+    stop_event: XmlStopEvent,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XmlStopEvent {
+    #[serde(default)]
+    previous_call: Vec<XmlCall>,
+    this_call: XmlCall,
+    #[serde(default)]
+    onward_call: Vec<XmlCall>,
+    service: XmlService,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XmlCall {
+    call_at_stop: XmlCallAtStop,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct XmlCallAtStop {
+    stop_point_ref: String,
+    stop_point_name: XmlText,
+    planned_quay: Option<XmlText>,
+    estimated_quay: Option<XmlText>,
+    service_arrival: Option<XmlServiceTime>,
+    service_departure: Option<XmlServiceTime>,
+    order: u32,
+    #[serde(default)]
+    not_serviced_stop: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XmlServiceTime {
+    timetabled_time: DateTime<Utc>,
+    estimated_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct XmlService {
+    operating_day_ref: NaiveDate,
+    journey_ref: String,
+    public_code: Option<String>,
+    line_ref: Option<String>,
+    direction_ref: Option<String>,
+    mode: Option<XmlMode>,
+    published_service_name: Option<XmlText>,
+    train_number: Option<String>,
+    operator_ref: Option<String>,
+    #[serde(default)]
+    cancelled: bool,
+    origin_stop_point_ref: Option<String>,
+    origin_text: Option<XmlText>,
+    destination_stop_point_ref: Option<String>,
+    destination_text: Option<XmlText>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XmlMode {
+    pt_mode: String,
+    short_name: Option<XmlText>,
+}
+
+/// `InternationalTextStructure` with a single `Text`.
+#[derive(Deserialize)]
+pub(crate) struct XmlText {
+    #[serde(rename = "Text")]
+    pub(crate) text: String,
+}
+
+impl From<XmlStopEventResult> for StopEvent {
+    fn from(result: XmlStopEventResult) -> Self {
+        let event = result.stop_event;
+        StopEvent {
+            id: result.id,
+            previous_calls: event.previous_call.into_iter().map(Into::into).collect(),
+            this_call: event.this_call.into(),
+            onward_calls: event.onward_call.into_iter().map(Into::into).collect(),
+            operator: event
+                .service
+                .operator_ref
+                .as_deref()
+                .and_then(Operator::from_company_code),
+            service: event.service.into(),
+        }
+    }
+}
+
+impl From<XmlCall> for CallAtStop {
+    fn from(call: XmlCall) -> Self {
+        call.call_at_stop.into()
+    }
+}
+
+impl From<XmlCallAtStop> for CallAtStop {
+    fn from(call: XmlCallAtStop) -> Self {
+        CallAtStop {
+            stop_point_ref: call.stop_point_ref,
+            stop_point_name: call.stop_point_name.text,
+            planned_quay: call.planned_quay.map(|quay| quay.text),
+            estimated_quay: call.estimated_quay.map(|quay| quay.text),
+            arrival: call.service_arrival.map(Into::into),
+            departure: call.service_departure.map(Into::into),
+            order: call.order,
+            not_serviced: call.not_serviced_stop,
+        }
+    }
+}
+
+impl From<XmlServiceTime> for ServiceTime {
+    fn from(time: XmlServiceTime) -> Self {
+        ServiceTime {
+            timetabled: time.timetabled_time,
+            estimated: time.estimated_time,
+        }
+    }
+}
+
+impl From<XmlService> for Service {
+    fn from(service: XmlService) -> Self {
+        let (mode, mode_short_name) = match service.mode {
+            Some(mode) => (Some(mode.pt_mode), mode.short_name.map(|name| name.text)),
+            None => (None, None),
+        };
+        Service {
+            operating_day: service.operating_day_ref,
+            journey_ref: service.journey_ref,
+            public_code: service.public_code,
+            line_ref: service.line_ref,
+            direction_ref: service.direction_ref,
+            mode,
+            mode_short_name,
+            published_service_name: service.published_service_name.map(|name| name.text),
+            train_number: service.train_number,
+            operator_ref: service.operator_ref,
+            cancelled: service.cancelled,
+            origin_stop_point_ref: service.origin_stop_point_ref,
+            origin: service.origin_text.map(|text| text.text),
+            destination_stop_point_ref: service.destination_stop_point_ref,
+            destination: service.destination_text.map(|text| text.text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = include_str!("../test_data/stop_event_response.xml");
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn fields_of_the_fixture() {
+        let events = parse_stop_events(RESPONSE).unwrap();
+        assert_eq!(events.len(), 3);
+
+        let ic8 = &events[0];
+        assert_eq!(ic8.id, "ID-6A0F8B7C-1");
+        assert_eq!(
+            ic8.service.operating_day,
+            NaiveDate::from_ymd_opt(2026, 1, 31).unwrap()
+        );
+        assert_eq!(ic8.service.journey_ref, "ch:1:sjyid:100001:825-001");
+        assert_eq!(ic8.service.public_code.as_deref(), Some("IC8"));
+        assert_eq!(ic8.service.line_ref.as_deref(), Some("ojp:91008:A"));
+        assert_eq!(ic8.service.mode.as_deref(), Some("rail"));
+        assert_eq!(ic8.service.mode_short_name.as_deref(), Some("IC"));
+        assert_eq!(ic8.train_id(), Some(TrainId::new(Operator::Sbb, 825)));
+        assert!(!ic8.service.cancelled);
+        assert_eq!(ic8.service.origin.as_deref(), Some("Brig"));
+        assert_eq!(ic8.service.destination.as_deref(), Some("Romanshorn"));
+        assert_eq!(
+            ic8.service.destination_stop_point_ref.as_deref(),
+            Some("8506121")
+        );
+
+        let bern = &ic8.this_call;
+        assert_eq!(bern.uic(), Some(8507000));
+        assert_eq!(bern.stop_point_name, "Bern");
+        assert_eq!(bern.order, 5);
+        assert_eq!(bern.planned_quay.as_deref(), Some("7"));
+        assert_eq!(bern.estimated_quay.as_deref(), Some("8"));
+        assert_eq!(bern.quay(), Some("8"));
+        assert!(bern.quay_changed());
+        assert_eq!(
+            bern.arrival,
+            Some(ServiceTime {
+                timetabled: at("2026-01-31T14:52:00Z"),
+                estimated: Some(at("2026-01-31T14:54:00Z")),
+            })
+        );
+        let departure = bern.departure.unwrap();
+        assert_eq!(departure.timetabled, at("2026-01-31T15:04:00Z"));
+        assert_eq!(departure.delay(), Some(TimeDelta::minutes(1)));
+        assert!(!bern.not_serviced);
+
+        let names: Vec<&str> = ic8
+            .calls()
+            .map(|call| call.stop_point_name.as_str())
+            .collect();
+        assert_eq!(names, ["Brig", "Spiez", "Bern", "Zürich HB", "Romanshorn"]);
+        let romanshorn = ic8.onward_calls.last().unwrap();
+        assert_eq!(romanshorn.quay(), Some("3"));
+        assert!(!romanshorn.quay_changed());
+        assert_eq!(romanshorn.departure, None);
+        assert_eq!(romanshorn.arrival.unwrap().delay(), None);
+        assert_eq!(ic8.last_arrival(), Some(at("2026-01-31T17:01:00Z")));
+
+        let ic61 = &events[1];
+        assert_eq!(ic61.service.public_code.as_deref(), Some("IC61"));
+        assert!(ic61.onward_calls.last().unwrap().not_serviced);
+
+        let ir15 = &events[2];
+        assert_eq!(ir15.train_number(), Some(2519));
+        assert!(ir15.service.cancelled);
+        assert!(ir15.previous_calls.is_empty());
+    }
+
+    #[test]
+    fn bad_result_is_skipped() {
+        let xml = RESPONSE.replacen(
+            "<OperatingDayRef>2026-01-31</OperatingDayRef>",
+            "<OperatingDayRef>31.01.2026</OperatingDayRef>",
+            1,
+        );
+        let results = parse_stop_event_results(&xml).unwrap();
+        assert_eq!(results.len(), 3);
+        let Err(OtdError::Xml { path, position, .. }) = &results[0] else {
+            panic!("first result should fail: {:?}", results[0]);
+        };
+        assert_eq!(path, "StopEventResult[0].StopEvent.Service.OperatingDayRef");
+        let cleaned = strip_namespaces(&xml);
+        let day = cleaned.find("31.01.2026").unwrap() as u64;
+        assert!((day..day + 60).contains(position), "{}", position);
+
+        let events = parse_stop_events(&xml).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].id, "ID-6A0F8B7C-2");
+    }
+
+    #[test]
+    fn broken_document_fails() {
+        let xml = &RESPONSE[..RESPONSE.len() / 2];
+        assert!(matches!(
+            parse_stop_events(xml),
+            Err(OtdError::Xml { position, .. }) if position > 0
+        ));
+    }
+}
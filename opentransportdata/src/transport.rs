@@ -0,0 +1,226 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{OtdError, parse_retry_after};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    pub timeout: Option<Duration>,
+}
+
+impl HttpRequest {
+    pub fn get(url: impl Into<String>) -> Self {
+        HttpRequest {
+            method: Method::Get,
+            url: url.into(),
+            headers: Vec::new(),
+            body: None,
+            timeout: None,
+        }
+    }
+
+    pub fn post(url: impl Into<String>, body: impl Into<String>) -> Self {
+        HttpRequest {
+            method: Method::Post,
+            url: url.into(),
+            headers: Vec::new(),
+            body: Some(body.into()),
+            timeout: None,
+        }
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn ok(body: impl Into<String>) -> Self {
+        HttpResponse::with_status(200, body)
+    }
+
+    pub fn with_status(status: u16, body: impl Into<String>) -> Self {
+        HttpResponse {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Case-insensitive header lookup.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Turn a non-success response into [`OtdError::Http`], keeping the `Retry-After` delay.
+    pub fn error_for_status(self) -> Result<Self, OtdError> {
+        if (200..300).contains(&self.status) {
+            return Ok(self);
+        }
+        Err(OtdError::Http {
+            status: self.status,
+            retry_after: self
+                .header("Retry-After")
+                .and_then(|v| parse_retry_after(v, chrono::Utc::now())),
+        })
+    }
+}
+
+/// Sends HTTP requests for the clients. Swap it out to run the clients
+/// against something other than the live API.
+pub trait Transport: Send + Sync {
+    fn send(
+        &self,
+        request: HttpRequest,
+    ) -> impl Future<Output = Result<HttpResponse, OtdError>> + Send;
+}
+
+#[cfg(feature = "native-client")]
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "native-client")]
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        ReqwestTransport::default()
+    }
+
+    pub fn from_client(client: reqwest::Client) -> Self {
+        ReqwestTransport { client }
+    }
+}
+
+#[cfg(feature = "native-client")]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, OtdError> {
+        let mut builder = match request.method {
+            Method::Get => self.client.get(&request.url),
+            Method::Post => self.client.post(&request.url),
+        };
+        for (name, value) in request.headers.iter() {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        let response = builder.send().await?;
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let body = response.text().await?;
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// Serves canned responses, e.g. the fixtures in `test_data/`.
+///
+/// A route matches when its pattern is contained in the request URL or body;
+/// the first matching route answers. Unmatched requests get a `404`.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTransport {
+    routes: Arc<Mutex<Vec<Route>>>,
+    requests: Arc<Mutex<Vec<HttpRequest>>>,
+}
+
+#[derive(Debug, Clone)]
+struct Route {
+    pattern: String,
+    response: HttpResponse,
+    /// Removed after answering once.
+    once: bool,
+}
+
+impl Route {
+    fn matches(&self, request: &HttpRequest) -> bool {
+        request.url.contains(self.pattern.as_str())
+            || request
+                .body
+                .as_deref()
+                .is_some_and(|body| body.contains(self.pattern.as_str()))
+    }
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        MemoryTransport::default()
+    }
+
+    pub fn route(self, pattern: impl Into<String>, response: HttpResponse) -> Self {
+        self.add_route(pattern.into(), response, false)
+    }
+
+    /// Answer the next matching request only, e.g. a `429` before the
+    /// fixture.
+    pub fn route_once(self, pattern: impl Into<String>, response: HttpResponse) -> Self {
+        self.add_route(pattern.into(), response, true)
+    }
+
+    fn add_route(self, pattern: String, response: HttpResponse, once: bool) -> Self {
+        self.routes.lock().unwrap().push(Route {
+            pattern,
+            response,
+            once,
+        });
+        self
+    }
+
+    /// Every request sent so far, oldest first.
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Transport for MemoryTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, OtdError> {
+        let response = {
+            let mut routes = self.routes.lock().unwrap();
+            match routes.iter().position(|route| route.matches(&request)) {
+                Some(index) if routes[index].once => routes.remove(index).response,
+                Some(index) => routes[index].response.clone(),
+                None => HttpResponse::with_status(404, ""),
+            }
+        };
+        self.requests.lock().unwrap().push(request);
+        Ok(response)
+    }
+}
@@ -0,0 +1,499 @@
+use std::fmt;
+
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::stop_event::{XmlCallAtStop, XmlService, parse_delivery};
+use crate::{CallAtStop, FormationAtScheduledStop, FormationResponse, OtdError, Service};
+
+/// Every call of one journey, from an `OJPTripInfoDelivery`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TripInfo {
+    /// In journey order, including stops that are no longer served.
+    pub calls: Vec<CallAtStop>,
+    /// `None` if the request did not include the service.
+    pub service: Option<Service>,
+}
+
+/// A stop of the journey as the timetable and the formation API see it.
+/// At least one side is set.
+#[derive(Debug, Clone, Copy)]
+pub struct JoinedStop<'a> {
+    pub call: Option<&'a CallAtStop>,
+    pub formation: Option<&'a FormationAtScheduledStop>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiscrepancyKind {
+    /// The formation lists the stop, the timetable no longer serves it.
+    Cancelled,
+    /// Only the formation lists the stop.
+    NotInTimetable,
+    /// Only the timetable lists the stop.
+    NotInFormation,
+    ArrivalTime {
+        formation: DateTime<FixedOffset>,
+        timetable: DateTime<Utc>,
+    },
+    DepartureTime {
+        formation: DateTime<FixedOffset>,
+        timetable: DateTime<Utc>,
+    },
+    /// The formation's track differs from the timetable's current quay.
+    Platform {
+        formation: String,
+        timetable: String,
+    },
+}
+
+/// Where the timetable and the formation API disagree about a stop.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discrepancy {
+    pub uic: u32,
+    pub name: String,
+    pub kind: DiscrepancyKind,
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.name)?;
+        match &self.kind {
+            DiscrepancyKind::Cancelled => write!(f, "cancelled in the timetable"),
+            DiscrepancyKind::NotInTimetable => write!(f, "missing in the timetable"),
+            DiscrepancyKind::NotInFormation => write!(f, "missing in the formation"),
+            DiscrepancyKind::ArrivalTime {
+                formation,
+                timetable,
+            } => write!(
+                f,
+                "arrival {} in the formation, {} in the timetable",
+                formation.format("%H:%M"),
+                timetable.with_timezone(formation.offset()).format("%H:%M")
+            ),
+            DiscrepancyKind::DepartureTime {
+                formation,
+                timetable,
+            } => write!(
+                f,
+                "departure {} in the formation, {} in the timetable",
+                formation.format("%H:%M"),
+                timetable.with_timezone(formation.offset()).format("%H:%M")
+            ),
+            DiscrepancyKind::Platform {
+                formation,
+                timetable,
+            } => write!(
+                f,
+                "track {} in the formation, {} in the timetable",
+                formation, timetable
+            ),
+        }
+    }
+}
+
+impl TripInfo {
+    pub fn cancelled_stops(&self) -> impl Iterator<Item = &CallAtStop> {
+        self.calls.iter().filter(|call| call.not_serviced)
+    }
+
+    /// Pair the calls with the formation's stops by UIC number, keeping
+    /// journey order. Stops only one side knows are included unpaired.
+    pub fn join<'a>(&'a self, formation: &'a FormationResponse) -> Vec<JoinedStop<'a>> {
+        let stops = &formation.formations_at_scheduled_stops;
+        let mut joined = Vec::with_capacity(stops.len().max(self.calls.len()));
+        let mut next = 0;
+        for call in self.calls.iter() {
+            let found = call.uic().and_then(|uic| {
+                stops[next..]
+                    .iter()
+                    .position(|stop| stop.scheduled_stop.stop_point.uic == uic)
+            });
+            let Some(offset) = found else {
+                joined.push(JoinedStop {
+                    call: Some(call),
+                    formation: None,
+                });
+                continue;
+            };
+            for stop in stops[next..next + offset].iter() {
+                joined.push(JoinedStop {
+                    call: None,
+                    formation: Some(stop),
+                });
+            }
+            joined.push(JoinedStop {
+                call: Some(call),
+                formation: Some(&stops[next + offset]),
+            });
+            next += offset + 1;
+        }
+        for stop in stops[next..].iter() {
+            joined.push(JoinedStop {
+                call: None,
+                formation: Some(stop),
+            });
+        }
+        joined
+    }
+
+    /// Stops where the two APIs disagree, in journey order. Pass-through
+    /// stops of the formation are not expected in the timetable.
+    pub fn discrepancies(&self, formation: &FormationResponse) -> Vec<Discrepancy> {
+        let mut found = Vec::new();
+        for stop in self.join(formation) {
+            let mut report = |kind| {
+                found.push(Discrepancy {
+                    uic: stop.uic().unwrap_or_default(),
+                    name: stop.name().to_string(),
+                    kind,
+                })
+            };
+            match (stop.call, stop.formation) {
+                (Some(call), Some(_)) if call.not_serviced => {
+                    report(DiscrepancyKind::Cancelled);
+                }
+                (Some(call), Some(formation)) => {
+                    let planned = &formation.scheduled_stop.stop_time;
+                    if let (Some(arrival), Some(planned)) = (call.arrival, planned.arrival_time)
+                        && arrival.timetabled != planned
+                    {
+                        report(DiscrepancyKind::ArrivalTime {
+                            formation: planned,
+                            timetable: arrival.timetabled,
+                        });
+                    }
+                    if let (Some(departure), Some(planned)) =
+                        (call.departure, planned.departure_time)
+                        && departure.timetabled != planned
+                    {
+                        report(DiscrepancyKind::DepartureTime {
+                            formation: planned,
+                            timetable: departure.timetabled,
+                        });
+                    }
+                    let track = formation.scheduled_stop.track.as_str();
+                    if let Some(quay) = call.quay()
+                        && !track.is_empty()
+                        && platform(track) != platform(quay)
+                    {
+                        report(DiscrepancyKind::Platform {
+                            formation: track.to_string(),
+                            timetable: quay.to_string(),
+                        });
+                    }
+                }
+                (None, Some(formation)) if !formation.scheduled_stop.stop_type.contains('D') => {
+                    report(DiscrepancyKind::NotInTimetable);
+                }
+                (Some(call), None) if !call.not_serviced => {
+                    report(DiscrepancyKind::NotInFormation);
+                }
+                _ => {}
+            }
+        }
+        found
+    }
+}
+
+impl JoinedStop<'_> {
+    pub fn uic(&self) -> Option<u32> {
+        match (self.formation, self.call) {
+            (Some(formation), _) => Some(formation.scheduled_stop.stop_point.uic),
+            (None, Some(call)) => call.uic(),
+            (None, None) => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match (self.call, self.formation) {
+            (Some(call), _) => call.stop_point_name.as_str(),
+            (None, Some(formation)) => formation.scheduled_stop.stop_point.name.as_str(),
+            (None, None) => "",
+        }
+    }
+}
+
+/// Platform number without sector letters, e.g. `7` for `7AB`.
+fn platform(track: &str) -> &str {
+    let trimmed = track.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    if trimmed.is_empty() { track } else { trimmed }
+}
+
+/// Read the result of an OJP `TripInfoRequest`. `None` if the response has
+/// no `TripInfoResult`, e.g. for an unknown journey.
+pub fn parse_trip_info(xml: &str) -> Result<Option<TripInfo>, OtdError> {
+    Ok(parse_delivery(xml)?
+        .and_then(|delivery| delivery.trip_info_delivery)
+        .and_then(|delivery| delivery.result)
+        .map(|result| TripInfo {
+            calls: result
+                .previous_call
+                .into_iter()
+                .chain(result.onward_call)
+                .map(CallAtStop::from)
+                .collect(),
+            service: result.service.map(Service::from),
+        }))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct XmlTripInfoDelivery {
+    #[serde(rename = "TripInfoResult")]
+    result: Option<XmlTripInfoResult>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XmlTripInfoResult {
+    #[serde(default)]
+    previous_call: Vec<XmlCallAtStop>,
+    #[serde(default)]
+    onward_call: Vec<XmlCallAtStop>,
+    service: Option<XmlService>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_formation_json;
+    use chrono::TimeDelta;
+
+    const RESPONSE: &str = include_str!("../test_data/trip_info_response.xml");
+    const FORMATION: &str = include_str!("../test_data/test_response.json");
+
+    fn trip() -> TripInfo {
+        parse_trip_info(RESPONSE).unwrap().unwrap()
+    }
+
+    fn formation() -> FormationResponse {
+        parse_formation_json(FORMATION).unwrap()
+    }
+
+    fn kinds(trip: &TripInfo, formation: &FormationResponse) -> Vec<(String, DiscrepancyKind)> {
+        trip.discrepancies(formation)
+            .into_iter()
+            .map(|discrepancy| (discrepancy.name, discrepancy.kind))
+            .collect()
+    }
+
+    #[test]
+    fn fields_of_the_fixture() {
+        let trip = trip();
+        assert_eq!(trip.calls.len(), 12);
+        assert!(trip.calls.iter().map(|call| call.order).eq(1..=12));
+        assert_eq!(trip.calls[0].stop_point_name, "Brig");
+        assert_eq!(trip.calls[0].arrival, None);
+        assert_eq!(
+            trip.calls[0].departure.unwrap().estimated,
+            Some("2026-01-31T13:46:00Z".parse().unwrap())
+        );
+        assert_eq!(trip.calls[11].stop_point_name, "Romanshorn");
+        assert_eq!(trip.calls[11].departure, None);
+
+        let zurich = &trip.calls[5];
+        assert_eq!(zurich.uic(), Some(8503000));
+        assert_eq!(zurich.quay(), Some("33"));
+        assert!(zurich.quay_changed());
+
+        let cancelled: Vec<&str> = trip
+            .cancelled_stops()
+            .map(|call| call.stop_point_name.as_str())
+            .collect();
+        assert_eq!(cancelled, vec!["Frauenfeld"]);
+
+        let service = trip.service.unwrap();
+        assert_eq!(service.journey_ref, "ch:1:sjyid:100001:825-001");
+        assert_eq!(service.train_number.as_deref(), Some("825"));
+        assert_eq!(service.operator_ref.as_deref(), Some("11"));
+    }
+
+    #[test]
+    fn unknown_journey() {
+        let start = RESPONSE.find("<TripInfoResult>").unwrap();
+        let end = RESPONSE.find("</TripInfoResult>").unwrap() + "</TripInfoResult>".len();
+        let empty = format!("{}{}", &RESPONSE[..start], &RESPONSE[end..]);
+        assert_eq!(parse_trip_info(&empty).unwrap(), None);
+    }
+
+    #[test]
+    fn join_pairs_every_stop_of_the_fixture() {
+        let trip = trip();
+        let formation = formation();
+        let joined = trip.join(&formation);
+        assert_eq!(joined.len(), 12);
+        for (stop, call) in joined.iter().zip(&trip.calls) {
+            assert_eq!(stop.call, Some(call));
+            assert_eq!(
+                stop.formation.unwrap().scheduled_stop.stop_point.uic,
+                call.uic().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn fixture_discrepancies() {
+        let trip = trip();
+        let formation = formation();
+        let found = trip.discrepancies(&formation);
+        assert_eq!(
+            found,
+            vec![
+                Discrepancy {
+                    uic: 8507100,
+                    name: "Thun".to_string(),
+                    kind: DiscrepancyKind::DepartureTime {
+                        formation: "2026-01-31T15:33:00+01:00".parse().unwrap(),
+                        timetable: "2026-01-31T14:34:00Z".parse().unwrap(),
+                    },
+                },
+                Discrepancy {
+                    uic: 8503000,
+                    name: "Zürich HB".to_string(),
+                    kind: DiscrepancyKind::Platform {
+                        formation: "34".to_string(),
+                        timetable: "33".to_string(),
+                    },
+                },
+                Discrepancy {
+                    uic: 8506100,
+                    name: "Frauenfeld".to_string(),
+                    kind: DiscrepancyKind::Cancelled,
+                },
+            ]
+        );
+        let lines: Vec<String> = found.iter().map(Discrepancy::to_string).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Thun: departure 15:33 in the formation, 15:34 in the timetable",
+                "Zürich HB: track 34 in the formation, 33 in the timetable",
+                "Frauenfeld: cancelled in the timetable",
+            ]
+        );
+    }
+
+    #[test]
+    fn platform_sectors_are_ignored() {
+        let trip = trip();
+        let mut formation = formation();
+        formation.formations_at_scheduled_stops[5]
+            .scheduled_stop
+            .track = "33D".to_string();
+        let names: Vec<String> = kinds(&trip, &formation)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["Thun", "Frauenfeld"]);
+    }
+
+    #[test]
+    fn stops_only_one_side_knows() {
+        let mut trip = trip();
+        let mut formation = formation();
+        // Visp is gone from the timetable, Thun is a pass-through stop of
+        // the formation
+        trip.calls
+            .retain(|call| !["Visp", "Thun"].contains(&call.stop_point_name.as_str()));
+        formation.formations_at_scheduled_stops[3]
+            .scheduled_stop
+            .stop_type = "D".to_string();
+        // an extra stop before Bern, and a skipped one the formation never had
+        let mut extra = trip.calls[2].clone();
+        extra.stop_point_ref = "8507010".to_string();
+        extra.stop_point_name = "Bern Wankdorf".to_string();
+        let mut skipped = extra.clone();
+        skipped.stop_point_ref = "8507002".to_string();
+        skipped.stop_point_name = "Ostermundigen".to_string();
+        skipped.not_serviced = true;
+        trip.calls.insert(2, skipped);
+        trip.calls.insert(2, extra);
+
+        let joined = trip.join(&formation);
+        let names: Vec<&str> = joined.iter().map(JoinedStop::name).collect();
+        assert_eq!(
+            &names[..7],
+            [
+                "Brig",
+                "Visp",
+                "Spiez",
+                "Bern Wankdorf",
+                "Ostermundigen",
+                "Thun",
+                "Bern"
+            ]
+        );
+        assert!(joined[1].call.is_none());
+        assert!(joined[3].formation.is_none());
+        assert_eq!(joined[3].uic(), Some(8507010));
+
+        let found = kinds(&trip, &formation);
+        assert_eq!(
+            &found[..2],
+            [
+                ("Visp".to_string(), DiscrepancyKind::NotInTimetable),
+                ("Bern Wankdorf".to_string(), DiscrepancyKind::NotInFormation),
+            ]
+        );
+        assert_eq!(found.len(), 4);
+    }
+
+    #[test]
+    fn time_changes() {
+        let mut trip = trip();
+        let mut formation = formation();
+        formation.formations_at_scheduled_stops[4]
+            .scheduled_stop
+            .stop_time
+            .arrival_time = Some("2026-01-31T15:55:00+01:00".parse().unwrap());
+        trip.calls[4].departure.as_mut().unwrap().timetabled =
+            "2026-01-31T15:04:00Z".parse().unwrap();
+
+        let found = trip.discrepancies(&formation);
+        let bern: Vec<String> = found
+            .iter()
+            .filter(|discrepancy| discrepancy.uic == 8507000)
+            .map(Discrepancy::to_string)
+            .collect();
+        assert_eq!(
+            bern,
+            vec![
+                "Bern: arrival 15:55 in the formation, 15:53 in the timetable",
+                "Bern: departure 16:02 in the formation, 16:04 in the timetable",
+            ]
+        );
+        assert_eq!(found.len(), 5);
+    }
+
+    #[test]
+    fn delays_of_the_joined_stops() {
+        let trip = trip();
+        let formation = formation();
+        let delays: Vec<(&str, Option<TimeDelta>)> = trip
+            .join(&formation)
+            .into_iter()
+            .take(6)
+            .map(|stop| {
+                let call = stop.call.unwrap();
+                let time = call.arrival.or(call.departure).unwrap();
+                (call.stop_point_name.as_str(), time.delay())
+            })
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                ("Brig", Some(TimeDelta::minutes(1))),
+                ("Visp", Some(TimeDelta::minutes(1))),
+                ("Spiez", Some(TimeDelta::minutes(2))),
+                ("Thun", Some(TimeDelta::minutes(1))),
+                ("Bern", Some(TimeDelta::minutes(1))),
+                ("Zürich HB", None),
+            ]
+        );
+        // a delayed train is not a timetable change
+        assert!(
+            trip.discrepancies(&formation)
+                .iter()
+                .all(|discrepancy| discrepancy.uic != 8507483)
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+
+use crate::transport::HttpResponse;
+use crate::{FormationResponse, TrainId};
+
+/// What the API last said about a train's formation on one day.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    /// `ETag` header, sent back as `If-None-Match`.
+    pub etag: Option<String>,
+    /// `Last-Modified` header, sent back as `If-Modified-Since`.
+    pub last_modified: Option<String>,
+    pub last_update: Option<DateTime<FixedOffset>>,
+}
+
+/// Result of a conditional fetch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormationUpdate {
+    /// First fetch of the train, or a new `lastUpdate`.
+    Changed(Box<FormationResponse>),
+    /// `304 Not Modified`, or the same `lastUpdate` as before.
+    Unchanged,
+}
+
+impl FormationUpdate {
+    pub fn is_changed(&self) -> bool {
+        matches!(self, FormationUpdate::Changed(_))
+    }
+}
+
+/// Validators per train and operation day, shared by every clone.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateTracker {
+    inner: Arc<Mutex<HashMap<(TrainId, NaiveDate), Validators>>>,
+}
+
+impl UpdateTracker {
+    pub fn new() -> Self {
+        UpdateTracker::default()
+    }
+
+    pub fn get(&self, train: &TrainId, operation_date: NaiveDate) -> Option<Validators> {
+        self.inner
+            .lock()
+            .unwrap()
+            .get(&(train.clone(), operation_date))
+            .cloned()
+    }
+
+    /// Remember `formation`'s `lastUpdate`, e.g. for one restored from
+    /// storage, so fetching it again counts as unchanged.
+    pub fn remember(&self, formation: &FormationResponse, operation_date: NaiveDate) {
+        self.inner
+            .lock()
+            .unwrap()
+            .entry((formation.train_id(), operation_date))
+            .or_default()
+            .last_update = Some(formation.last_update);
+    }
+
+    /// Forget the days before `operation_date`.
+    pub fn clear_before(&self, operation_date: NaiveDate) {
+        self.inner
+            .lock()
+            .unwrap()
+            .retain(|(_, date), _| *date >= operation_date);
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Store the validators of a `200` response and tell whether its
+    /// formation changed.
+    pub(crate) fn record(
+        &self,
+        formation: FormationResponse,
+        operation_date: NaiveDate,
+        response: &HttpResponse,
+    ) -> FormationUpdate {
+        let mut trains = self.inner.lock().unwrap();
+        let validators = trains
+            .entry((formation.train_id(), operation_date))
+            .or_default();
+        validators.etag = response.header("ETag").map(str::to_string);
+        validators.last_modified = response.header("Last-Modified").map(str::to_string);
+        if validators.last_update == Some(formation.last_update) {
+            return FormationUpdate::Unchanged;
+        }
+        validators.last_update = Some(formation.last_update);
+        FormationUpdate::Changed(Box::new(formation))
+    }
+}
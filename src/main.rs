@@ -2,9 +2,13 @@ use dioxus::prelude::*;
 use std::{sync::Arc, time::Duration};
 
 mod components;
+#[cfg(feature = "server")]
+mod monitoring;
+#[cfg(feature = "server")]
+mod storage;
 mod views;
 
-use views::{All, Home};
+use views::{All, Home, Stats};
 
 #[derive(Debug, Clone, Routable, PartialEq)]
 #[rustfmt::skip]
@@ -12,7 +16,9 @@ enum Route {
     #[route("/")]
     Home {},
     #[route("/all")]
-    All {}
+    All {},
+    #[route("/stats")]
+    Stats {}
 }
 
 const FAVICON: Asset = asset!("/assets/favicon.ico");
@@ -29,19 +35,80 @@ const TAILWIND_CSS: Asset = asset!("/assets/tailwind.css",
 // const SBB_WEB_BOLD_WOFF2: Asset = asset!("/assets/Grafikdateien-SBB-Font/WEB/SBBWeb-Bold.woff2");
 // const SBB_WEB_BOLD_WOFF: Asset = asset!("/assets/Grafikdateien-SBB-Font/WEB/SBBWeb-Bold.woff");
 
-use chrono::Datelike;
-
 #[cfg(feature = "server")]
 use once_cell::sync::Lazy;
 #[cfg(feature = "server")]
 use std::sync::RwLock;
 
-use opentransportdata::{parse_formation_json, FormationResponse};
+use opentransportdata::{
+    parse_formation_json, Forecast, FormationResponse, LineCatalog, RuleSet, Statistics,
+};
+#[cfg(feature = "server")]
+use opentransportdata::{
+    ChangeKind, DiscoveryQuery, Family, FormationClient, FormationUpdate, Forecaster, HubStop,
+    OjpClient, OtdError, RefreshScheduler, RollingStockCatalog, StopEvent, Transport,
+};
+#[cfg(feature = "server")]
+use futures_util::StreamExt;
 
 #[cfg(feature = "server")]
 static TRAINS: Lazy<Arc<RwLock<Vec<FormationResponse>>>> =
     Lazy::new(|| Arc::new(RwLock::new(Vec::new())));
 
+/// `None` if the database at `DATABASE_PATH` cannot be opened; the server
+/// then runs from memory only.
+#[cfg(feature = "server")]
+static STORAGE: Lazy<Option<storage::Storage>> = Lazy::new(|| {
+    let path = std::env::var("DATABASE_PATH").unwrap_or_else(|_| "deklassiert.sqlite".into());
+    match storage::Storage::open(&path) {
+        Ok(storage) => Some(storage),
+        Err(e) => {
+            println!("Error opening database {}: {}", path, e);
+            None
+        }
+    }
+});
+
+/// Tomorrow's forecast, computed once per service day.
+#[cfg(feature = "server")]
+static FORECAST: Lazy<RwLock<Option<(chrono::NaiveDate, Vec<Forecast>)>>> =
+    Lazy::new(|| RwLock::new(None));
+
+/// Detection rules from the TOML file at `RULES_PATH`, else the built-in
+/// ones. Loaded once in `main`, so an invalid file stops the server.
+#[cfg(feature = "server")]
+static RULES: Lazy<RuleSet> = Lazy::new(|| match std::env::var("RULES_PATH") {
+    Ok(path) => {
+        let rules = RuleSet::from_path(&path).expect("load RULES_PATH");
+        println!("Loaded {} rules from {}", rules.rules.len(), path);
+        rules
+    }
+    Err(_) => RuleSet::builtin().clone(),
+});
+
+/// Monitored lines from the TOML file at `LINES_PATH`, else the built-in
+/// catalog. Loaded once in `main`, like the rules.
+#[cfg(feature = "server")]
+static LINES: Lazy<LineCatalog> = Lazy::new(|| match std::env::var("LINES_PATH") {
+    Ok(path) => {
+        let lines = LineCatalog::from_path(&path).expect("load LINES_PATH");
+        println!("Loaded {} lines from {}", lines.lines.len(), path);
+        lines
+    }
+    Err(_) => LineCatalog::builtin().clone(),
+});
+
+/// Run `f` on the blocking thread pool, so SQLite does not hold up the
+/// runtime. `None` without a database.
+#[cfg(feature = "server")]
+async fn with_storage<T: Send + 'static>(
+    f: impl FnOnce(&storage::Storage) -> rusqlite::Result<T> + Send + 'static,
+) -> Option<rusqlite::Result<T>> {
+    tokio::task::spawn_blocking(move || STORAGE.as_ref().map(f))
+        .await
+        .expect("database task panicked")
+}
+
 #[cfg(feature = "server")]
 fn load_trains_from_dir(dir: &str) -> Vec<FormationResponse> {
     let mut trains = Vec::new();
@@ -61,105 +128,262 @@ fn load_trains_from_dir(dir: &str) -> Vec<FormationResponse> {
     trains
 }
 
+/// Hubs from `DISCOVERY_HUBS`, by station name or UIC number. Empty if the
+/// variable is unset or nothing resolved.
+#[cfg(feature = "server")]
+async fn configured_hubs<T: Transport>(ojp_client: &OjpClient<T>) -> Vec<HubStop> {
+    let Ok(names) = std::env::var("DISCOVERY_HUBS") else {
+        return Vec::new();
+    };
+    let mut hubs = Vec::new();
+    for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let station = match name.parse::<u32>() {
+            Ok(uic) => ojp_client.stop_by_uic(uic).await,
+            Err(_) => ojp_client.station_by_name(name).await,
+        };
+        match station {
+            Ok(Some(station)) => {
+                println!("Discovery hub {}: {} ({})", name, station.name, station.uic);
+                hubs.push(station.into());
+            }
+            Ok(None) => println!("Unknown discovery hub {}", name),
+            Err(e) => println!("Error resolving discovery hub {}: {}", name, e),
+        }
+    }
+    hubs
+}
+
+/// Time between two searches for the day's trains.
+#[cfg(feature = "server")]
+const DISCOVERY_INTERVAL: chrono::TimeDelta = chrono::TimeDelta::hours(1);
+/// Formations of older operation days are deleted at day rollover; the
+/// forecast only needs to know which trains ran.
+#[cfg(feature = "server")]
+const FORMATION_RETENTION: chrono::Days = chrono::Days::new(14);
+/// How often the scheduler is checked for trains due for a refresh.
+#[cfg(feature = "server")]
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+
 #[cfg(feature = "server")]
 pub fn start_tabs_reload_task() {
-    std::thread::spawn(|| {
+    tokio::spawn(async move {
         dotenv::dotenv().ok();
 
         let formation_token = std::env::var("FORMATION_TOKEN").expect("TOKEN not set");
         let ojp_token = std::env::var("OJP_TOKEN").expect("set OJP_TOKEN env var");
-
-        let is_train_finished = |train: &FormationResponse, now: chrono::DateTime<chrono::Utc>| {
-            let last_time = train
-                .formations_at_scheduled_stops
-                .iter()
-                .rev()
-                .find_map(|stop| {
-                    stop.scheduled_stop
-                        .stop_time
-                        .departure_time
-                        .or(stop.scheduled_stop.stop_time.arrival_time)
-                });
-            match last_time {
-                Some(time) => time.with_timezone(&chrono::Utc) < now,
-                None => false,
+        let formation_client = FormationClient::new(formation_token);
+        let ojp_client = OjpClient::new(ojp_token);
+        monitoring::register("formation", formation_client.config(), None);
+        // Discovery asks OJP every hour, formations are only fetched while trains run.
+        monitoring::register("ojp", ojp_client.config(), Some(DISCOVERY_INTERVAL * 2));
+        monitoring::heartbeat();
+
+        let mut scheduler = RefreshScheduler::new();
+        let mut today = DiscoveryQuery::today().operation_date;
+
+        // Serve what an earlier run fetched today until the first refresh is done.
+        if let Some(stored) = with_storage(move |storage| storage.load_day(today)).await {
+            let now_utc = chrono::Utc::now();
+            match stored {
+                Ok(stored) => {
+                    let (stored, fetched_at): (Vec<_>, Vec<_>) = stored
+                        .into_iter()
+                        .filter(|(formation, _)| !formation.is_finished(now_utc))
+                        .unzip();
+                    // Schedule them as if just fetched, so a restart does not
+                    // refetch every train at once.
+                    for formation in stored.iter() {
+                        scheduler.fetched(formation, now_utc);
+                        formation_client.updates().remember(formation, today);
+                    }
+                    match fetched_at.iter().min() {
+                        Some(oldest) => println!(
+                            "Restored {} trains from the database, up to {} minutes old",
+                            stored.len(),
+                            (now_utc - *oldest).num_minutes()
+                        ),
+                        None => println!("Restored 0 trains from the database"),
+                    }
+                    *TRAINS.write().unwrap() = stored;
+                }
+                Err(e) => println!("Error restoring trains: {}", e),
             }
-        };
+        }
 
-        loop {
-            let trains = opentransportdata::fetch_train_numbers(&ojp_token);
+        let hubs = configured_hubs(&ojp_client).await;
+        let mut events: Vec<StopEvent> = Vec::new();
+        let mut next_discovery = chrono::Utc::now();
 
+        loop {
             let now_utc = chrono::Utc::now();
-            let today = now_utc.date_naive();
-            let year = today.year();
-            let month = today.month();
-            let day = today.day();
+            monitoring::heartbeat();
+
+            if now_utc >= next_discovery {
+                let mut query = DiscoveryQuery::today().lines(LINES.clone());
+                if !hubs.is_empty() {
+                    query = query.hubs(hubs.clone());
+                }
+                if query.operation_date != today {
+                    // A new service day: yesterday's finished trains run again.
+                    today = query.operation_date;
+                    scheduler = RefreshScheduler::new();
+                    formation_client.updates().clear_before(today);
+                    let before = today - FORMATION_RETENTION;
+                    match with_storage(move |storage| storage.prune_formations(before)).await {
+                        Some(Ok(pruned)) => {
+                            println!("Pruned {} formations before {}", pruned, before)
+                        }
+                        Some(Err(e)) => println!("Error pruning formations: {}", e),
+                        None => {}
+                    }
+                }
+                match ojp_client.discover_events(&query).await {
+                    Ok(found) => {
+                        for error in found.skipped.iter() {
+                            println!("Skipped stop event: {}", error);
+                        }
+                        for event in found.unidentified.iter() {
+                            println!(
+                                "Train {}: unknown operator {}, skipping",
+                                event.service.train_number.as_deref().unwrap_or("?"),
+                                event.service.operator_ref.as_deref().unwrap_or("?")
+                            );
+                        }
+                        events = found.events;
+                        for train in events.iter().filter_map(StopEvent::train_id) {
+                            scheduler.add(train, now_utc);
+                        }
+                        println!("Discovered {} trains", scheduler.len());
+                    }
+                    Err(e) => {
+                        println!("Error fetching train numbers: {}", e);
+                    }
+                }
+                next_discovery = now_utc + DISCOVERY_INTERVAL;
+            }
 
             let mut train_map = {
                 let guard = TRAINS.read().unwrap();
                 guard
                     .iter()
                     .cloned()
-                    .map(|t| (t.train_meta_information.train_number, t))
+                    .map(|t| (t.train_id(), t))
                     .collect::<std::collections::BTreeMap<_, _>>()
             };
 
-            println!("Loaded trains: {:?}", trains);
-
-            match trains {
-                Ok(trains) => {
-                    'train_loop: for train in trains {
-                        'load_train: loop {
-                            println!("Loading formation for train {}", train);
-                            match opentransportdata::get_train_formation(
-                                train,
-                                year,
-                                month,
-                                day,
-                                &formation_token,
-                            ) {
-                                Err(e) => {
-                                    if e.contains("Too Many Requests") {
-                                        println!("Rate limit hit, sleeping for 60 seconds");
-                                        std::thread::sleep(Duration::from_secs(60));
-                                        continue 'load_train;
-                                    } else if e.contains("Forbidden") {
-                                        println!("Forbidden for train {}, skipping", train);
-                                        std::thread::sleep(Duration::from_secs(12));
-                                        continue 'train_loop ;
-                                    } else if e.contains("Bad Request") {
-                                        println!("Bad request for train {}, skipping", train);
-                                        std::thread::sleep(Duration::from_secs(12));
-                                        continue 'train_loop;
-                                    }
+            let due = scheduler.due(now_utc);
+            if !due.is_empty() {
+                println!("Loading formations for {} trains", due.len());
+                let mut results = std::pin::pin!(formation_client.fetch_updates(due, today));
+                while let Some((train, result)) = results.next().await {
+                    monitoring::heartbeat();
+                    match result {
+                        Err(e) => {
+                            match e {
+                                OtdError::Http { status: 403, .. } => {
+                                    println!("Forbidden for train {}, skipping", train);
+                                }
+                                OtdError::Http { status: 400, .. } => {
+                                    println!("Bad request for train {}, skipping", train);
+                                }
+                                e => {
                                     println!("Error loading formation for train {}: {}", train, e);
-                                    break 'load_train;
                                 }
-                                Ok(formation) => {
-                                    train_map.insert(formation.train_meta_information.train_number, formation);
-                                    let mut guard = TRAINS.write().unwrap();
-                                    *guard = train_map.values().cloned().collect();
-                                    break 'load_train;
+                            }
+                            scheduler.failed(&train, chrono::Utc::now());
+                        }
+                        Ok(FormationUpdate::Unchanged) => {
+                            // Nothing to store; only the delays may have moved.
+                            match train_map.get_mut(&train) {
+                                Some(previous) => {
+                                    for event in events.iter() {
+                                        if event.train_id().as_ref() == Some(&train) {
+                                            previous.apply_realtime(event);
+                                        }
+                                    }
+                                    scheduler.fetched(previous, chrono::Utc::now());
                                 }
+                                None => scheduler.remove(&train),
                             }
                         }
-
-                        std::thread::sleep(Duration::from_secs(12));
+                        Ok(FormationUpdate::Changed(formation)) => {
+                            let mut formation = *formation;
+                            println!(
+                                "Train {}: formation updated at {}",
+                                train,
+                                formation.last_update.format("%H:%M")
+                            );
+                            for event in events.iter() {
+                                if event.train_id().as_ref() == Some(&train) {
+                                    formation.apply_realtime(event);
+                                }
+                            }
+                            let mut seen = std::collections::HashSet::new();
+                            for diagnostic in opentransportdata::diagnose_formation(&formation) {
+                                if seen.insert(diagnostic.token.clone()) {
+                                    println!("Train {}: {}", train, diagnostic);
+                                }
+                            }
+                            let catalog = RollingStockCatalog::builtin();
+                            for diff in formation.composition_diffs().iter() {
+                                if diff.coupled_coaches() == 0 {
+                                    continue;
+                                }
+                                let ew_iv = diff
+                                    .changes
+                                    .iter()
+                                    .filter(|c| c.kind == ChangeKind::Coupled && c.is_coach())
+                                    .filter_map(|c| catalog.identify(&c.vehicle))
+                                    .filter(|stock| stock.family == Family::EwIv)
+                                    .count();
+                                println!(
+                                    "Train {}: {} coaches coupled at {} ({} EW IV)",
+                                    train,
+                                    diff.coupled_coaches(),
+                                    diff.to.name,
+                                    ew_iv
+                                );
+                            }
+                            let stored = formation.clone();
+                            let fetched_at = chrono::Utc::now();
+                            if let Some(Err(e)) = with_storage(move |storage| {
+                                storage.save(&stored, today, fetched_at)
+                            })
+                            .await
+                            {
+                                println!("Error storing train {}: {}", train, e);
+                            }
+                            let sightings =
+                                opentransportdata::sightings(&formation, today, &RULES, &LINES);
+                            if let Some(Err(e)) =
+                                with_storage(move |storage| storage.save_sightings(&sightings))
+                                    .await
+                            {
+                                println!("Error archiving train {}: {}", train, e);
+                            }
+                            match scheduler.fetched(&formation, chrono::Utc::now()) {
+                                Some(next) => println!(
+                                    "Train {}: next refresh at {}",
+                                    train,
+                                    next.with_timezone(&chrono::Local).format("%H:%M")
+                                ),
+                                None => println!("Train {}: finished", train),
+                            }
+                            train_map.insert(formation.train_id(), formation);
+                            *TRAINS.write().unwrap() = train_map.values().cloned().collect();
+                        }
                     }
-
-                    // Remove trains that have already passed.
-                    train_map.retain(|_, formation| !is_train_finished(formation, now_utc));
-
-                    let mut guard = TRAINS.write().unwrap();
-                    *guard = train_map.values().cloned().collect();
-                }
-                Err(e) => {
-                    println!("Error fetching train numbers: {}", e);
                 }
+
+                println!("Formation API budget: {:?}", formation_client.budget());
             }
 
-            std::thread::sleep(Duration::from_secs(3600));
-            println!("Trains reloaded");
+            // Remove trains that have already passed.
+            train_map.retain(|_, formation| !formation.is_finished(now_utc));
+
+            *TRAINS.write().unwrap() = train_map.values().cloned().collect();
+
+            tokio::time::sleep(SCHEDULER_TICK).await;
         }
     });
 }
@@ -169,6 +393,50 @@ async fn get_trains() -> Result<Vec<FormationResponse>, ServerFnError> {
     Ok(TRAINS.read().unwrap().clone())
 }
 
+/// The rules the server flags deklassiert coaches with.
+#[server]
+async fn get_rules() -> Result<RuleSet, ServerFnError> {
+    Ok(RULES.clone())
+}
+
+/// The lines the server monitors.
+#[server]
+async fn get_lines() -> Result<LineCatalog, ServerFnError> {
+    Ok(LINES.clone())
+}
+
+/// Counts over every archived deklassiert coach. Empty without a database.
+#[server]
+async fn get_stats() -> Result<Statistics, ServerFnError> {
+    let Some(stored) =
+        with_storage(|storage| Ok((storage.load_sightings()?, storage.load_run_days()?))).await
+    else {
+        return Ok(Statistics::default());
+    };
+    let (sightings, observed) = stored.map_err(ServerFnError::new)?;
+    Ok(Statistics::from_sightings(&sightings, &observed))
+}
+
+/// Trains likely to run with a deklassiert coach tomorrow, most likely first.
+#[server]
+async fn get_forecast() -> Result<Vec<Forecast>, ServerFnError> {
+    const LIKELY: f64 = 0.5;
+
+    let today = DiscoveryQuery::today().operation_date;
+    if let Some((day, forecasts)) = FORECAST.read().unwrap().as_ref()
+        && *day == today
+    {
+        return Ok(forecasts.clone());
+    }
+    let Some(observations) = with_storage(|storage| storage.load_observations()).await else {
+        return Ok(Vec::new());
+    };
+    let observations = observations.map_err(ServerFnError::new)?;
+    let forecasts = Forecaster::new(observations).likely(today + chrono::Days::new(1), LIKELY);
+    *FORECAST.write().unwrap() = Some((today, forecasts.clone()));
+    Ok(forecasts)
+}
+
 #[component]
 fn App() -> Element {
 //     let font_css = format!(
@@ -219,9 +487,12 @@ fn App() -> Element {
 #[cfg(feature = "server")]
 #[tokio::main]
 async fn main() {
+    use axum::routing::get;
     use axum::Router;
     use std::net::SocketAddr;
     dotenv::dotenv().ok();
+    Lazy::force(&RULES);
+    Lazy::force(&LINES);
 
     start_tabs_reload_task();
 
@@ -233,7 +504,10 @@ async fn main() {
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
     println!("Listening on http://{}", addr);
 
-    let router = Router::new().serve_dioxus_application(ServeConfig::default(), App);
+    let router = Router::new()
+        .route("/healthz", get(monitoring::healthz))
+        .route("/metrics", get(monitoring::metrics))
+        .serve_dioxus_application(ServeConfig::default(), App);
 
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
 
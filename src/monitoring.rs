@@ -0,0 +1,225 @@
+//! `/healthz` and `/metrics`, so monitoring can tell when the reload task
+//! died, a token expired or the data went stale.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::RwLock;
+
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use chrono::{DateTime, TimeDelta, Utc};
+use once_cell::sync::Lazy;
+use opentransportdata::{ClientConfig, DiscoveryQuery, RateLimiter, RequestStats};
+
+use crate::{LINES, RULES, TRAINS};
+
+/// The reload task counts as dead after this long without a heartbeat.
+const HEARTBEAT_TIMEOUT: TimeDelta = TimeDelta::minutes(30);
+
+/// An API the server talks to.
+struct Endpoint {
+    name: &'static str,
+    rate_limiter: RateLimiter,
+    requests: RequestStats,
+    /// Unhealthy without a successful response for this long.
+    stale_after: Option<TimeDelta>,
+    registered: DateTime<Utc>,
+}
+
+static ENDPOINTS: Lazy<RwLock<Vec<Endpoint>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Unix time of the reload task's last sign of life; 0 before it started.
+static HEARTBEAT: AtomicI64 = AtomicI64::new(0);
+
+/// Report on the client using `config`.
+pub fn register(name: &'static str, config: &ClientConfig, stale_after: Option<TimeDelta>) {
+    ENDPOINTS.write().unwrap().push(Endpoint {
+        name,
+        rate_limiter: config.rate_limiter.clone(),
+        requests: config.request_stats.clone(),
+        stale_after,
+        registered: Utc::now(),
+    });
+}
+
+/// Called by the reload task whenever it makes progress.
+pub fn heartbeat() {
+    HEARTBEAT.store(Utc::now().timestamp(), Ordering::Relaxed);
+}
+
+/// Why the server is unhealthy; empty if it is fine.
+fn problems(now: DateTime<Utc>) -> Vec<String> {
+    let mut problems = Vec::new();
+    let beat = HEARTBEAT.load(Ordering::Relaxed);
+    if beat == 0 {
+        problems.push("reload task not started".to_string());
+    } else if let Some(beat) = DateTime::from_timestamp(beat, 0)
+        && now - beat > HEARTBEAT_TIMEOUT
+    {
+        problems.push(format!("reload task silent since {}", beat.to_rfc3339()));
+    }
+    for endpoint in ENDPOINTS.read().unwrap().iter() {
+        let counts = endpoint.requests.counts();
+        if let Some(status @ (401 | 403)) = counts.last_status {
+            problems.push(format!("{}: last response {}", endpoint.name, status));
+        }
+        let since = counts.last_success.unwrap_or(endpoint.registered);
+        if let Some(stale_after) = endpoint.stale_after
+            && now - since > stale_after
+        {
+            problems.push(format!(
+                "{}: no successful response since {}",
+                endpoint.name,
+                since.to_rfc3339()
+            ));
+        }
+    }
+    problems
+}
+
+pub async fn healthz() -> impl IntoResponse {
+    let problems = problems(Utc::now());
+    if problems.is_empty() {
+        (StatusCode::OK, "ok\n".to_string())
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("{}\n", problems.join("\n")),
+        )
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Prometheus text format.
+pub async fn metrics() -> impl IntoResponse {
+    let mut out = String::new();
+
+    let (trains, coaches) = {
+        let trains = TRAINS.read().unwrap();
+        let today = DiscoveryQuery::today().operation_date;
+        let coaches: usize = trains
+            .iter()
+            .map(|train| opentransportdata::sightings(train, today, &RULES, &LINES).len())
+            .sum();
+        (trains.len(), coaches)
+    };
+    metric(
+        &mut out,
+        "deklassiert_trains",
+        "gauge",
+        "Trains currently served.",
+    );
+    let _ = writeln!(out, "deklassiert_trains {}", trains);
+    metric(
+        &mut out,
+        "deklassiert_coaches",
+        "gauge",
+        "Deklassiert coaches on the trains currently served.",
+    );
+    let _ = writeln!(out, "deklassiert_coaches {}", coaches);
+
+    metric(
+        &mut out,
+        "deklassiert_reload_heartbeat_timestamp_seconds",
+        "gauge",
+        "Last sign of life of the reload task.",
+    );
+    let _ = writeln!(
+        out,
+        "deklassiert_reload_heartbeat_timestamp_seconds {}",
+        HEARTBEAT.load(Ordering::Relaxed)
+    );
+
+    let endpoints = ENDPOINTS.read().unwrap();
+    let counts: Vec<_> = endpoints
+        .iter()
+        .map(|endpoint| (endpoint, endpoint.requests.counts()))
+        .collect();
+
+    metric(
+        &mut out,
+        "deklassiert_last_success_timestamp_seconds",
+        "gauge",
+        "Last successful response per endpoint.",
+    );
+    for (endpoint, counts) in counts.iter() {
+        if let Some(last_success) = counts.last_success {
+            let _ = writeln!(
+                out,
+                "deklassiert_last_success_timestamp_seconds{{endpoint=\"{}\"}} {}",
+                endpoint.name,
+                last_success.timestamp()
+            );
+        }
+    }
+
+    metric(
+        &mut out,
+        "deklassiert_http_responses_total",
+        "counter",
+        "Responses per endpoint and HTTP status.",
+    );
+    for (endpoint, counts) in counts.iter() {
+        for (status, count) in counts.by_status.iter() {
+            let _ = writeln!(
+                out,
+                "deklassiert_http_responses_total{{endpoint=\"{}\",status=\"{}\"}} {}",
+                endpoint.name, status, count
+            );
+        }
+    }
+
+    metric(
+        &mut out,
+        "deklassiert_transport_errors_total",
+        "counter",
+        "Requests that got no response, e.g. timeouts.",
+    );
+    for (endpoint, counts) in counts.iter() {
+        let _ = writeln!(
+            out,
+            "deklassiert_transport_errors_total{{endpoint=\"{}\"}} {}",
+            endpoint.name, counts.transport_errors
+        );
+    }
+
+    let limiter_stats: Vec<_> = endpoints
+        .iter()
+        .map(|endpoint| (endpoint.name, endpoint.rate_limiter.stats()))
+        .collect();
+
+    metric(
+        &mut out,
+        "deklassiert_rate_limit_waits_total",
+        "counter",
+        "Requests put to sleep by the rate limiter.",
+    );
+    for (name, stats) in limiter_stats.iter() {
+        let _ = writeln!(
+            out,
+            "deklassiert_rate_limit_waits_total{{endpoint=\"{}\"}} {}",
+            name, stats.waits
+        );
+    }
+
+    metric(
+        &mut out,
+        "deklassiert_rate_limit_wait_seconds_total",
+        "counter",
+        "Time requests slept in the rate limiter.",
+    );
+    for (name, stats) in limiter_stats.iter() {
+        let _ = writeln!(
+            out,
+            "deklassiert_rate_limit_wait_seconds_total{{endpoint=\"{}\"}} {}",
+            name,
+            stats.waited.as_secs_f64()
+        );
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
@@ -0,0 +1,352 @@
+//! Fetched formations in SQLite, so a restart can serve the current service
+//! day before the first refresh has finished, and an archive of every
+//! deklassiert coach seen.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use opentransportdata::{parse_formation_json, FormationResponse, Observation, Sighting, TrainId};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS formations (
+    operator TEXT NOT NULL,
+    train_number INTEGER NOT NULL,
+    operation_date TEXT NOT NULL,
+    fetched_at TEXT NOT NULL,
+    formation TEXT NOT NULL,
+    PRIMARY KEY (operator, train_number, operation_date)
+);
+CREATE TABLE IF NOT EXISTS runs (
+    operator TEXT NOT NULL,
+    train_number INTEGER NOT NULL,
+    operation_date TEXT NOT NULL,
+    PRIMARY KEY (operator, train_number, operation_date)
+);
+INSERT OR IGNORE INTO runs (operator, train_number, operation_date)
+    SELECT operator, train_number, operation_date FROM formations;
+CREATE TABLE IF NOT EXISTS sightings (
+    operator TEXT NOT NULL,
+    train_number INTEGER NOT NULL,
+    operation_date TEXT NOT NULL,
+    coach TEXT NOT NULL,
+    sighting TEXT NOT NULL,
+    PRIMARY KEY (operator, train_number, operation_date, coach)
+);
+";
+
+/// The latest formation of every train and operation day, and the
+/// deklassiert coaches seen on them. Formations are pruned after a while;
+/// `runs` keeps which trains ran on which day for the forecast.
+pub struct Storage {
+    conn: Mutex<Connection>,
+}
+
+impl Storage {
+    /// Open or create the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Create the tables missing in `conn`.
+    fn with_connection(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Storage {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Store `formation` as the train's latest for `operation_date`.
+    pub fn save(
+        &self,
+        formation: &FormationResponse,
+        operation_date: NaiveDate,
+        fetched_at: DateTime<Utc>,
+    ) -> rusqlite::Result<()> {
+        let json = serde_json::to_string(formation)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let train = formation.train_id();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO formations
+                 (operator, train_number, operation_date, fetched_at, formation)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                train.operator.as_str(),
+                train.train_number,
+                operation_date.to_string(),
+                fetched_at.to_rfc3339(),
+                json
+            ],
+        )?;
+        tx.execute(
+            "INSERT OR IGNORE INTO runs (operator, train_number, operation_date)
+             VALUES (?1, ?2, ?3)",
+            params![
+                train.operator.as_str(),
+                train.train_number,
+                operation_date.to_string()
+            ],
+        )?;
+        tx.commit()
+    }
+
+    /// Delete the formations of operation days before `before`. Returns how
+    /// many were deleted.
+    pub fn prune_formations(&self, before: NaiveDate) -> rusqlite::Result<usize> {
+        self.conn.lock().unwrap().execute(
+            "DELETE FROM formations WHERE operation_date < ?1",
+            params![before.to_string()],
+        )
+    }
+
+    /// Every stored formation of `operation_date` with the time it was
+    /// fetched, by operator and train number.
+    pub fn load_day(
+        &self,
+        operation_date: NaiveDate,
+    ) -> rusqlite::Result<Vec<(FormationResponse, DateTime<Utc>)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT formation, fetched_at FROM formations
+             WHERE operation_date = ?1
+             ORDER BY operator, train_number",
+        )?;
+        let rows = statement.query_map(params![operation_date.to_string()], |row| {
+            let json: String = row.get(0)?;
+            let fetched_at: String = row.get(1)?;
+            let formation = parse_formation_json(&json).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e))
+            })?;
+            let fetched_at = DateTime::parse_from_rfc3339(&fetched_at).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(1, Type::Text, Box::new(e))
+            })?;
+            Ok((formation, fetched_at.with_timezone(&Utc)))
+        })?;
+        rows.collect()
+    }
+
+    /// Record `sightings`. A coach seen again on the same train and day
+    /// keeps only its latest stop range.
+    pub fn save_sightings(&self, sightings: &[Sighting]) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for sighting in sightings {
+            let json = serde_json::to_string(sighting)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            tx.execute(
+                "INSERT OR REPLACE INTO sightings
+                     (operator, train_number, operation_date, coach, sighting)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    sighting.train.operator.as_str(),
+                    sighting.train.train_number,
+                    sighting.operation_date.to_string(),
+                    sighting.coach(),
+                    json
+                ],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// Every recorded sighting, oldest day first.
+    pub fn load_sightings(&self) -> rusqlite::Result<Vec<Sighting>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT sighting FROM sightings
+             ORDER BY operation_date, operator, train_number",
+        )?;
+        let rows = statement.query_map([], |row| {
+            let json: String = row.get(0)?;
+            serde_json::from_str(&json)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e)))
+        })?;
+        rows.collect()
+    }
+
+    /// Every operation day a formation was stored for, oldest first.
+    pub fn load_run_days(&self) -> rusqlite::Result<Vec<NaiveDate>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement =
+            conn.prepare("SELECT DISTINCT operation_date FROM runs ORDER BY operation_date")?;
+        let rows = statement.query_map([], |row| {
+            let date: String = row.get(0)?;
+            date.parse()
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e)))
+        })?;
+        rows.collect()
+    }
+
+    /// One observation per stored train and operation day, deklassiert if
+    /// any coach was sighted on it.
+    pub fn load_observations(&self) -> rusqlite::Result<Vec<Observation>> {
+        // Line of every run with a sighting; all sightings of a run share it.
+        let lines: HashMap<(TrainId, NaiveDate), Option<String>> = self
+            .load_sightings()?
+            .into_iter()
+            .map(|sighting| ((sighting.train, sighting.operation_date), sighting.line))
+            .collect();
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT operator, train_number, operation_date FROM runs
+             ORDER BY operation_date, operator, train_number",
+        )?;
+        let rows = statement.query_map([], |row| {
+            let operator: String = row.get(0)?;
+            let date: String = row.get(2)?;
+            let operation_date = date.parse().map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(2, Type::Text, Box::new(e))
+            })?;
+            let train = TrainId::new(operator.into(), row.get(1)?);
+            let line = lines.get(&(train.clone(), operation_date));
+            Ok(Observation {
+                train,
+                operation_date,
+                deklassiert: line.is_some(),
+                line: line.cloned().flatten(),
+            })
+        })?;
+        rows.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentransportdata::{Operator, StopPoint};
+
+    const FORMATION: &str = include_str!("../opentransportdata/test_data/test_response.json");
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    fn fetched_at() -> DateTime<Utc> {
+        "2026-01-31T16:50:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn formations_round_trip() {
+        let storage = Storage::open(":memory:").unwrap();
+        let formation = parse_formation_json(FORMATION).unwrap();
+        storage
+            .save(&formation, day("2026-01-31"), fetched_at())
+            .unwrap();
+        // Saving again replaces the stored formation.
+        storage
+            .save(&formation, day("2026-01-31"), fetched_at())
+            .unwrap();
+
+        assert_eq!(
+            storage.load_day(day("2026-01-31")).unwrap(),
+            vec![(formation, fetched_at())]
+        );
+        assert!(storage.load_day(day("2026-02-01")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn pruning_keeps_the_runs() {
+        let storage = Storage::open(":memory:").unwrap();
+        let formation = parse_formation_json(FORMATION).unwrap();
+        storage
+            .save(&formation, day("2026-01-30"), fetched_at())
+            .unwrap();
+        storage
+            .save(&formation, day("2026-01-31"), fetched_at())
+            .unwrap();
+
+        assert_eq!(storage.prune_formations(day("2026-01-31")).unwrap(), 1);
+        assert!(storage.load_day(day("2026-01-30")).unwrap().is_empty());
+        assert_eq!(storage.load_day(day("2026-01-31")).unwrap().len(), 1);
+        assert_eq!(storage.prune_formations(day("2026-01-31")).unwrap(), 0);
+
+        assert_eq!(
+            storage.load_run_days().unwrap(),
+            vec![day("2026-01-30"), day("2026-01-31")]
+        );
+        assert_eq!(storage.load_observations().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn observations_from_sightings() {
+        let storage = Storage::open(":memory:").unwrap();
+        let formation = parse_formation_json(FORMATION).unwrap();
+        let stop = |uic, name: &str| StopPoint {
+            uic,
+            name: name.to_string(),
+        };
+        let sightings = vec![
+            Sighting {
+                operation_date: day("2026-01-31"),
+                train: formation.train_id(),
+                line: Some("IC8".to_string()),
+                evn: Some("50 85 19-94 012-8".to_string()),
+                order_number: Some(5),
+                from: stop(8501609, "Brig"),
+                to: stop(8507000, "Bern"),
+            },
+            Sighting {
+                operation_date: day("2026-01-31"),
+                train: formation.train_id(),
+                line: Some("IC8".to_string()),
+                evn: None,
+                order_number: Some(6),
+                from: stop(8507000, "Bern"),
+                to: stop(8503000, "Zürich HB"),
+            },
+        ];
+        storage
+            .save(&formation, day("2026-01-30"), fetched_at())
+            .unwrap();
+        storage
+            .save(&formation, day("2026-01-31"), fetched_at())
+            .unwrap();
+        storage.save_sightings(&sightings).unwrap();
+        // A coach seen again is stored once.
+        storage.save_sightings(&sightings).unwrap();
+
+        assert_eq!(storage.load_sightings().unwrap(), sightings);
+        let observations = storage.load_observations().unwrap();
+        assert_eq!(observations.len(), 2);
+        assert!(!observations[0].deklassiert);
+        assert_eq!(observations[0].line, None);
+        assert!(observations[1].deklassiert);
+        assert_eq!(observations[1].line.as_deref(), Some("IC8"));
+        assert_eq!(observations[1].train, formation.train_id());
+    }
+
+    #[test]
+    fn runs_are_backfilled_from_formations() {
+        // A database from before the `runs` table existed.
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE formations (
+                operator TEXT NOT NULL,
+                train_number INTEGER NOT NULL,
+                operation_date TEXT NOT NULL,
+                fetched_at TEXT NOT NULL,
+                formation TEXT NOT NULL,
+                PRIMARY KEY (operator, train_number, operation_date)
+            );
+            INSERT INTO formations VALUES ('SBBP', 825, '2026-01-30', '', '{}');
+            INSERT INTO formations VALUES ('SBBP', 825, '2026-01-31', '', '{}');",
+        )
+        .unwrap();
+
+        let storage = Storage::with_connection(conn).unwrap();
+        let observations = storage.load_observations().unwrap();
+        assert_eq!(observations.len(), 2);
+        assert_eq!(observations[0].train, TrainId::new(Operator::Sbb, 825));
+        assert_eq!(observations[0].operation_date, day("2026-01-30"));
+        assert!(
+            observations
+                .iter()
+                .all(|observation| !observation.deklassiert)
+        );
+    }
+}
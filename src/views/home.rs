@@ -1,7 +1,10 @@
-use crate::get_trains;
+use super::tooltip::vehicle_tooltip;
+use crate::{get_forecast, get_lines, get_rules, get_trains};
 use dioxus::prelude::*;
 use opentransportdata::{
-    parse_formation_for_stop, FormationResponse, Offer, StatusFlag, VehicleIdentifier, VehicleType,
+    parse_formation_for_stop_with_rules, CabEnd, Family, Forecast, FormationResponse, LineCatalog,
+    Offer, RollingStockCatalog, RuleSet, StatusFlag, StopPoint, VehicleIdentifier, VehicleType,
+    Verdict,
 };
 
 const ARROW_ICON: Asset = asset!("/assets/chevron-left-medium.svg");
@@ -16,7 +19,7 @@ const EW_IV_STEUERWAGEN_R_ICON: Asset = asset!("/assets/ew_iv_steuerwagen_r.svg"
 const CLOSED_CAR_ICON: Asset = asset!("/assets/closed_car.svg");
 const FIRST_CLASS_SVG: Asset = asset!("/assets/first_class.svg");
 const SECOND_CLASS_SVG: Asset = asset!("/assets/second_class.svg");
-// const DEKLASSIERT_CAR_ICON: Asset = asset!("/assets/deklassiert_car.svg");
+const DEKLASSIERT_CAR_ICON: Asset = asset!("/assets/deklassiert_car.svg");
 
 const DEKLASSIERT_EW_IV_ICON: Asset = asset!("/assets/deklassiert_ew_iv.svg");
 
@@ -29,17 +32,12 @@ const RESERVED_SVG: Asset = asset!("/assets/sbb-icons-main/icons/sa-r.svg");
 const GROUP_SVG: Asset = asset!("/assets/sbb-icons-main/icons/sa-reisegruppe.svg");
 const LOW_FLOOR_SVG: Asset = asset!("/assets/sbb-icons-main/icons/sa-nf.svg");
 
-// train number 600-649 is IC6/IC61
-// train number 800-849 is IC8/IC81
-// train number 950-999 is IC6/IC61
-const IC8_SVG: Asset = asset!("/assets/sbb-icons-main/icons/ic-8.svg");
-const IC81_SVG: Asset = asset!("/assets/sbb-icons-main/icons/ic-81.svg");
-const IC6_SVG: Asset = asset!("/assets/sbb-icons-main/icons/ic-6.svg");
-const IC61_SVG: Asset = asset!("/assets/sbb-icons-main/icons/ic-61.svg");
+// line logos are named in catalog/lines.toml
 const IC_SVG: Asset = asset!("/assets/sbb-icons-main/icons/ic.svg");
+const SBB_ICONS: Asset = asset!("/assets/sbb-icons-main/icons");
 
 #[component]
-fn TrainView(train: FormationResponse) -> Element {
+fn TrainView(train: FormationResponse, rules: RuleSet, lines: LineCatalog) -> Element {
     let mut selected = use_signal(|| select_current_or_next_stop(&train));
     let mut hover_vehicle = use_signal(|| None::<usize>);
     let mut pinned_vehicle = use_signal(|| None::<usize>);
@@ -50,12 +48,6 @@ fn TrainView(train: FormationResponse) -> Element {
         pinned_vehicle.set(None);
     });
 
-    let tabs_all = train
-        .formations_at_scheduled_stops
-        .iter()
-        .map(|s| s.scheduled_stop.stop_point.name.clone())
-        .collect::<Vec<_>>();
-
     let visible_stop_indices = visible_stop_indices(&train);
     if visible_stop_indices.is_empty() {
         return rsx! {
@@ -71,8 +63,8 @@ fn TrainView(train: FormationResponse) -> Element {
 
     let selected_index = selected();
     let deklassiert_target_id = format!(
-        "deklassiert-{}-{}",
-        train.train_meta_information.train_number, selected_index
+        "deklassiert-{}-{}-{}",
+        train.operator, train.train_meta_information.train_number, selected_index
     );
     let deklassiert_target_id_effect = deklassiert_target_id.clone();
 
@@ -95,23 +87,9 @@ fn TrainView(train: FormationResponse) -> Element {
     });
 
     let active_vehicle = hover_vehicle().or(pinned_vehicle());
-    let train_logo = if (800..850).contains(&train.train_meta_information.train_number) {
-        if tabs_all.contains(&"Interlaken Ost".to_string()) {
-            IC81_SVG
-        } else {
-            IC8_SVG
-        }
-    } else if (950..1000).contains(&train.train_meta_information.train_number)
-        || (600..650).contains(&train.train_meta_information.train_number)
-    {
-        if tabs_all.contains(&"Interlaken Ost".to_string()) {
-            IC61_SVG
-        } else {
-            IC6_SVG
-        }
-    } else {
-        IC_SVG
-    };
+    let line = lines.line_for_train(&train);
+    let train_logo = line_logo(line.and_then(|line| line.logo.as_deref()));
+    let last_update = train.last_update.format("%H:%M").to_string();
 
     rsx! {
         div { class: "tabs",
@@ -119,6 +97,7 @@ fn TrainView(train: FormationResponse) -> Element {
             div { class: "logo-row",
                 img { src: train_logo, class: "app-logo" }
                 "Nr {train.train_meta_information.train_number}"
+                span { class: "last-update", title: "Letzte Änderung der Formation", "Stand {last_update}" }
             }
             ul { class: "tab-list",
                 for (i, stop_index) in visible_stop_indices.clone().into_iter().enumerate() {
@@ -127,13 +106,16 @@ fn TrainView(train: FormationResponse) -> Element {
                         class: if selected() == stop_index { "tab active" } else { "tab" },
                         onclick: move |_| selected.set(stop_index),
                         "{train.formations_at_scheduled_stops[stop_index].scheduled_stop.stop_point.name}"
+                        if let Some((badge, title)) = composition_badge(&train, stop_index) {
+                            span { class: "tab-badge", title: "{title}", "{badge}" }
+                        }
                     }
                 }
             }
 
             div { class: "tab-panel",
                 {
-                    let mut cars = parse_formation_for_stop(&train, selected_index);
+                    let mut cars = parse_formation_for_stop_with_rules(&train, selected_index, &rules);
 
                     let stop = &train.formations_at_scheduled_stops[selected_index];
 
@@ -149,18 +131,20 @@ fn TrainView(train: FormationResponse) -> Element {
                         .departure_time
                         .map(|t| t.format("%H:%M").to_string());
 
+                    let arrival_delay = delay_label(stop.scheduled_stop.stop_time.arrival_delay());
+                    let departure_delay = delay_label(stop.scheduled_stop.stop_time.departure_delay());
 
                     let mut prev_had_lowfloor = false;
 
                     // filter out fictional and parked cars
                     cars = cars.iter().filter(|c| c.vehicle_type != VehicleType::Fictional && c.vehicle_type != VehicleType::Parked).cloned().collect::<Vec<_>>();
 
-                    let train_length = cars.len();
+                    let catalog = RollingStockCatalog::builtin();
                     let first_deklassiert_index = cars
                         .iter()
                         .position(|car| car.status.contains(&StatusFlag::Deklassiert));
 
-                    let rendered_cars: Vec<(Asset, Vec<Asset>, bool, Option<u32>, Option<char>, Option<VehicleIdentifier>)> =
+                    let rendered_cars: Vec<(Asset, Vec<Asset>, bool, Option<u32>, Option<char>, Option<VehicleIdentifier>, Option<StopPoint>, Option<Verdict>)> =
                         cars.iter().enumerate().filter_map(|(i,car)| {
 
                             // collect overlay icons
@@ -213,15 +197,7 @@ fn TrainView(train: FormationResponse) -> Element {
                                     if car.offers.contains(&Offer::LowFloor) {
                                         (IC2000_ICON, Some("2"), "class-overlay")
                                     } else {
-                                        if i == 0 {
-                                            // this is the first car, so show the steuerwagen!
-                                            (EW_IV_STEUERWAGEN_L_ICON, Some("2"), "class-overlay family-left")
-                                        } else if i == train_length - 1 {
-                                            // this is the last car, so show the steuerwagen!
-                                            (EW_IV_STEUERWAGEN_R_ICON, Some("2"), "class-overlay family-right")
-                                        } else {
-                                            (EW_IV_ICON, Some("2"), "class-overlay")
-                                        }
+                                        (EW_IV_ICON, Some("2"), "class-overlay")
                                     },
 
                                 VehicleType::DiningSecondClass =>
@@ -254,6 +230,27 @@ fn TrainView(train: FormationResponse) -> Element {
                                 _ => (IC2000_ICON, None, "class-overlay"),
                             };
 
+                            // the catalog knows the real coach, the guesses above only go by offers
+                            let stock = catalog.identify(car);
+                            if let Some(stock) = stock {
+                                match stock.family {
+                                    Family::Re460 => icon = LOCOMOTIVE_ICON,
+                                    Family::EwIv => {
+                                        icon = match catalog.cab_end(&cars, i) {
+                                            Some(CabEnd::Left) => EW_IV_STEUERWAGEN_L_ICON,
+                                            Some(CabEnd::Right) => EW_IV_STEUERWAGEN_R_ICON,
+                                            None => EW_IV_ICON,
+                                        };
+                                    }
+                                    Family::Ic2000 | Family::Ic2020 => {
+                                        if icon != FAMILY_CAR_L_ICON && icon != FAMILY_CAR_R_ICON {
+                                            icon = IC2000_ICON;
+                                        }
+                                    }
+                                    Family::Other(_) => {}
+                                }
+                            }
+
                             let is_family_right = icon == FAMILY_CAR_R_ICON  || icon == EW_IV_STEUERWAGEN_R_ICON;
 
                             // closed overrides icon + label
@@ -263,7 +260,10 @@ fn TrainView(train: FormationResponse) -> Element {
                             };
 
                             if car.status.contains(&StatusFlag::Deklassiert) {
-                                icon = DEKLASSIERT_EW_IV_ICON;
+                                icon = match stock.map(|s| &s.family) {
+                                    Some(Family::Ic2000 | Family::Ic2020) => DEKLASSIERT_CAR_ICON,
+                                    _ => DEKLASSIERT_EW_IV_ICON,
+                                };
                             };
 
                             if car.status.contains(&StatusFlag::Reserved) {
@@ -287,12 +287,12 @@ fn TrainView(train: FormationResponse) -> Element {
                             prev_had_lowfloor = car.offers.contains(&Offer::LowFloor);
 
                             let identifier = car.vehicle_identifier.clone();
-                            Some((icon, overlay_icons, is_family_right, car.order_number, car.sector, identifier))
+                            Some((icon, overlay_icons, is_family_right, car.order_number, car.sector, identifier, car.destination.clone(), car.deklassiert.clone()))
                         })
                         .collect();
 
                     let mut sector_groups: Vec<(Option<char>, usize)> = Vec::new();
-                    for (_, _, _, _, sector, _) in rendered_cars.iter() {
+                    for (_, _, _, _, sector, _, _, _) in rendered_cars.iter() {
                         if let Some(last) = sector_groups.last_mut() {
                             if last.0 == *sector {
                                 last.1 += 1;
@@ -301,6 +301,27 @@ fn TrainView(train: FormationResponse) -> Element {
                         }
                         sector_groups.push((*sector, 1));
                     }
+
+                    // only worth showing when the train splits
+                    let mut destination_groups: Vec<(Option<StopPoint>, usize)> = Vec::new();
+                    for (_, _, _, _, _, _, destination, _) in rendered_cars.iter() {
+                        if let Some(last) = destination_groups.last_mut() {
+                            if last.0 == *destination {
+                                last.1 += 1;
+                                continue;
+                            }
+                        }
+                        destination_groups.push((destination.clone(), 1));
+                    }
+                    let mut destinations: Vec<u32> = Vec::new();
+                    for (destination, _) in destination_groups.iter() {
+                        if let Some(destination) = destination {
+                            if !destinations.contains(&destination.uic) {
+                                destinations.push(destination.uic);
+                            }
+                        }
+                    }
+                    let show_destinations = destinations.len() > 1;
                     let vehicle_count = rendered_cars.len();
 
                     rsx! {
@@ -314,6 +335,9 @@ fn TrainView(train: FormationResponse) -> Element {
                                 span { class: "time-item",
                                     img { src: CLOCK_ICON, class: "clock-icon" }
                                     span { "Ankunft {a}" }
+                                    if let Some(delay) = arrival_delay {
+                                        span { class: "delay", "{delay}" }
+                                    }
                                 }
                             }
 
@@ -321,6 +345,9 @@ fn TrainView(train: FormationResponse) -> Element {
                                 span { class: "time-item",
                                     img { src: CLOCK_ICON, class: "clock-icon" }
                                     span { "Abfahrt {d}" }
+                                    if let Some(delay) = departure_delay {
+                                        span { class: "delay", "{delay}" }
+                                    }
                                 }
                             }
                         }
@@ -341,7 +368,7 @@ fn TrainView(train: FormationResponse) -> Element {
                                     }
                                 }
                                 div { class: "train-row", style: "grid-template-columns: repeat({vehicle_count}, var(--vehicle-width)); column-gap: var(--vehicle-gap);" ,
-                                    for (index, (icon, overlay_icons, is_family_right, order_number, _, identifier)) in rendered_cars.iter().enumerate() {
+                                    for (index, (icon, overlay_icons, is_family_right, order_number, _, identifier, _, deklassiert)) in rendered_cars.iter().enumerate() {
                                         {
                                             let vehicle_id = if first_deklassiert_index == Some(index) {
                                                 Some(deklassiert_target_id.clone())
@@ -374,7 +401,7 @@ fn TrainView(train: FormationResponse) -> Element {
                                                         img { src: *icon, class: "vehicle-icon" }
 
                                                     if active_vehicle == Some(index) {
-                                                        if let Some(text) = format_vehicle_identifier(identifier) {
+                                                        if let Some(text) = vehicle_tooltip(identifier, deklassiert) {
                                                             div { class: "vehicle-tooltip", "{text}" }
                                                         }
                                                     }
@@ -401,6 +428,30 @@ fn TrainView(train: FormationResponse) -> Element {
                                         }
                                     }
                                 }
+                                if show_destinations {
+                                    div { class: "destination-row", style: "grid-template-columns: repeat({vehicle_count}, var(--vehicle-width)); column-gap: var(--vehicle-gap);" ,
+                                        for (destination, count) in destination_groups.iter() {
+                                            {
+                                                let colour = destination
+                                                    .as_ref()
+                                                    .and_then(|d| destinations.iter().position(|uic| *uic == d.uic))
+                                                    .map(|i| i % 4);
+                                                rsx!(
+                                                    div {
+                                                        class: match colour {
+                                                            Some(i) => format!("destination-block destination-{i}"),
+                                                            None => "destination-block".to_string(),
+                                                        },
+                                                        style: "grid-column: span {count};",
+                                                        if let Some(destination) = destination {
+                                                            span { "nach {destination.name}" }
+                                                        }
+                                                    }
+                                                )
+                                            }
+                                        }
+                                    }
+                                }
                             }
                         }
                     }
@@ -411,31 +462,56 @@ fn TrainView(train: FormationResponse) -> Element {
     }
 }
 
-fn format_vehicle_identifier(identifier: &Option<VehicleIdentifier>) -> Option<String> {
-    let id = identifier.as_ref()?;
-    let mut parts: Vec<String> = Vec::new();
+/// The SBB icon named by the line's `logo`; the generic IC logo for a line
+/// without one.
+fn line_logo(logo: Option<&str>) -> String {
+    match logo {
+        Some(logo) => format!("{SBB_ICONS}/{logo}.svg"),
+        None => IC_SVG.to_string(),
+    }
+}
 
-    if let Some(name) = id.type_code_name.as_ref() {
-        if !name.is_empty() {
-            parts.push(name.clone());
-        }
+/// Display names of the monitored lines, e.g. "IC 81, IC 8 und IC 6".
+fn line_names(lines: &LineCatalog) -> String {
+    let names: Vec<&str> = lines
+        .lines
+        .iter()
+        .map(|line| line.display_name.as_str())
+        .collect();
+    match names.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} und {}", rest.join(", "), last),
+        Some((last, _)) => last.to_string(),
+        None => String::new(),
     }
-    if let Some(evn) = id.evn.as_ref() {
-        if !evn.is_empty() {
-            parts.push(format!("EVN {evn}"));
-        }
+}
+
+/// Short badge and tooltip for a stop tab when coaches are coupled or
+/// uncoupled there, e.g. "+3 Wagen ab Bern".
+fn composition_badge(train: &FormationResponse, stop_index: usize) -> Option<(String, String)> {
+    let diff = train.composition_diff(stop_index.checked_sub(1)?)?;
+    if diff.is_empty() {
+        return None;
     }
-    if let Some(parent) = id.parent_evn.as_ref() {
-        if !parent.is_empty() {
-            parts.push(format!("Parent {parent}"));
-        }
+    let mut parts = Vec::new();
+    if diff.coupled_coaches() > 0 {
+        parts.push(format!("+{}", diff.coupled_coaches()));
     }
-
+    if diff.uncoupled_coaches() > 0 {
+        parts.push(format!("−{}", diff.uncoupled_coaches()));
+    }
+    let stop_name = &diff.to.name;
     if parts.is_empty() {
-        None
-    } else {
-        Some(parts.join(" · "))
+        return Some(("±".to_string(), format!("Formation ändert ab {}", stop_name)));
     }
+    let badge = parts.join(" ");
+    let title = format!("{} Wagen ab {}", badge, stop_name);
+    Some((badge, title))
+}
+
+/// Delay in whole minutes, e.g. `+3'`. `None` when on time or unknown.
+fn delay_label(delay: Option<chrono::TimeDelta>) -> Option<String> {
+    let minutes = delay?.num_minutes();
+    (minutes != 0).then(|| format!("{:+}'", minutes))
 }
 
 fn visible_stop_indices(train: &FormationResponse) -> Vec<usize> {
@@ -460,12 +536,12 @@ fn select_current_or_next_stop(train: &FormationResponse) -> usize {
         let arrival = stop
             .scheduled_stop
             .stop_time
-            .arrival_time
+            .expected_arrival()
             .map(|t| t.with_timezone(&chrono::Utc));
         let departure = stop
             .scheduled_stop
             .stop_time
-            .departure_time
+            .expected_departure()
             .map(|t| t.with_timezone(&chrono::Utc));
 
         if let (Some(arrival), Some(departure)) = (arrival, departure) {
@@ -484,9 +560,52 @@ fn select_current_or_next_stop(train: &FormationResponse) -> usize {
     *visible.last().unwrap_or(&0)
 }
 
+/// E.g. `IC 8 812 (70 %)`.
+fn forecast_label(forecast: &Forecast, lines: &LineCatalog) -> String {
+    let line = forecast
+        .line
+        .as_deref()
+        .and_then(|id| lines.by_id(id))
+        .map_or("Zug", |line| line.display_name.as_str());
+    format!(
+        "{} {} ({:.0} %)",
+        line,
+        forecast.train.train_number,
+        forecast.probability * 100.0
+    )
+}
+
+/// Tomorrow's likely trains, fetched only when there is nothing to show
+/// today.
+#[component]
+fn Forecasts(lines: LineCatalog) -> Element {
+    let forecast_future = use_server_future(|| get_forecast())?;
+
+    // The forecast is optional, so failing to load it is not an error.
+    let forecasts = match &*forecast_future.read() {
+        Some(Ok(forecasts)) => forecasts.clone(),
+        _ => Vec::new(),
+    };
+
+    rsx! {
+        if !forecasts.is_empty() {
+            div { class: "container text-center forecast",
+                p { "Morgen wahrscheinlich mit deklassiertem Wagen:" }
+                ul { class: "forecast-list",
+                    for forecast in forecasts {
+                        li { "{forecast_label(&forecast, &lines)}" }
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[component]
 pub fn Home() -> Element {
     let trains_future = use_server_future(|| get_trains())?;
+    let rules_future = use_server_future(|| get_rules())?;
+    let lines_future = use_server_future(|| get_lines())?;
 
     let mut trains = match &*trains_future.read() {
         Some(Ok(trains)) => trains.clone(),
@@ -494,6 +613,17 @@ pub fn Home() -> Element {
         None => return rsx! { div { "Loading trains..." } },
     };
 
+    let rules = match &*rules_future.read() {
+        Some(Ok(rules)) => rules.clone(),
+        Some(Err(_)) => return rsx! { div { "Failed to load rules" } },
+        None => return rsx! { div { "Loading rules..." } },
+    };
+    let lines = match &*lines_future.read() {
+        Some(Ok(lines)) => lines.clone(),
+        Some(Err(_)) => return rsx! { div { "Failed to load lines" } },
+        None => return rsx! { div { "Loading lines..." } },
+    };
+
     // filter trains to only those with deklassiert coaches
     trains = trains
         .iter()
@@ -503,7 +633,7 @@ pub fn Home() -> Element {
                 .iter()
                 .enumerate()
                 .any(|(i, _stop)| {
-                    let vehicles = parse_formation_for_stop(train, i);
+                    let vehicles = parse_formation_for_stop_with_rules(train, i, &rules);
                     vehicles
                         .iter()
                         .filter(|v| v.status.contains(&StatusFlag::Deklassiert))
@@ -514,6 +644,7 @@ pub fn Home() -> Element {
         .cloned()
         .collect();
 
+    let monitored = line_names(&lines);
     let legend_items: Vec<(Asset, &str, &str, bool)> = vec![
         (
             LOCOMOTIVE_ICON,
@@ -561,14 +692,15 @@ pub fn Home() -> Element {
         main { id: "trains",
             if trains.is_empty() {
                 div { class: "container text-center mt-10",
-                    "Momentan sind leider keine deklassierten Wagen verfügbar, du kannst aber alle aktuellen IC6/61 und IC8/81 "
+                    "Momentan sind leider keine deklassierten Wagen verfügbar, du kannst aber alle aktuellen {monitored} "
                     a { href: "/all", strong { "hier " } }
                     "anschauen."
                 }
+                Forecasts { lines: lines.clone() }
             }
 
             for train in trains {
-                TrainView { train: train }
+                TrainView { train: train, rules: rules.clone(), lines: lines.clone() }
             }
         }
 
@@ -602,7 +734,12 @@ pub fn Home() -> Element {
                         class: "external-link",
                         strong { "opentransportdata" }
                     }
-                    " versuchen wir diese Wagen auf den IC6/61 und IC8/81 Linien zu erkennen und entsprechend zu markieren."
+                    " versuchen wir diese Wagen auf den Linien {monitored} zu erkennen und entsprechend zu markieren."
+                }
+                p { class: "block text-left whitespace-pre-line",
+                    "Wann bisher deklassierte Wagen unterwegs waren, zeigt die "
+                    a { href: "/stats", strong { "Statistik" } }
+                    "."
                 }
                 p { class: "block text-left whitespace-pre-line",
                     "Alle Angaben ohne Gewähr."
@@ -11,4 +11,7 @@
 mod home;
 pub use home::Home;
 mod all;
-pub use all::All;
\ No newline at end of file
+pub use all::All;
+mod stats;
+pub use stats::Stats;
+mod tooltip;
@@ -0,0 +1,145 @@
+use crate::{get_lines, get_stats};
+use dioxus::prelude::*;
+use opentransportdata::LineCatalog;
+
+const WEEKDAYS: [&str; 7] = [
+    "Montag",
+    "Dienstag",
+    "Mittwoch",
+    "Donnerstag",
+    "Freitag",
+    "Samstag",
+    "Sonntag",
+];
+
+/// Rows of one count table, with bars scaled to the largest count.
+#[component]
+fn CountTable(title: String, rows: Vec<(String, usize)>) -> Element {
+    let max = rows
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    rsx! {
+        section { class: "stats-section",
+            h2 { "{title}" }
+            if rows.is_empty() {
+                p { class: "stats-empty", "Noch keine Daten." }
+            }
+            table { class: "stats-table",
+                for (label, count) in rows {
+                    tr {
+                        th { "{label}" }
+                        td { class: "stats-bar-cell",
+                            div {
+                                class: "stats-bar",
+                                style: "width: {count * 100 / max}%",
+                            }
+                        }
+                        td { class: "stats-count", "{count}" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Coaches per observed day of each weekday, with bars scaled to the highest
+/// rate. Weekdays without an observed day show a dash.
+#[component]
+fn RateTable(title: String, rows: Vec<(String, Option<f64>, usize)>) -> Element {
+    let max = rows
+        .iter()
+        .filter_map(|(_, rate, _)| *rate)
+        .fold(0.0, f64::max);
+
+    rsx! {
+        section { class: "stats-section",
+            h2 { "{title}" }
+            table { class: "stats-table",
+                for (label, rate, days) in rows {
+                    tr {
+                        th { "{label}" }
+                        td { class: "stats-bar-cell",
+                            if let Some(rate) = rate.filter(|_| max > 0.0) {
+                                div {
+                                    class: "stats-bar",
+                                    style: "width: {rate / max * 100.0:.0}%",
+                                }
+                            }
+                        }
+                        td { class: "stats-count",
+                            match rate {
+                                Some(rate) => rsx! { "{rate:.1} ({days} Tage)" },
+                                None => rsx! { "–" },
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+pub fn Stats() -> Element {
+    let stats_future = use_server_future(|| get_stats())?;
+    let lines_future = use_server_future(|| get_lines())?;
+
+    let stats = match &*stats_future.read() {
+        Some(Ok(stats)) => stats.clone(),
+        Some(Err(_)) => return rsx! { div { "Failed to load statistics" } },
+        None => return rsx! { div { "Loading statistics..." } },
+    };
+    // Display names are optional; fall back to the built-in catalog.
+    let lines = match &*lines_future.read() {
+        Some(Ok(lines)) => lines.clone(),
+        _ => LineCatalog::default(),
+    };
+
+    let by_weekday: Vec<(String, Option<f64>, usize)> = WEEKDAYS
+        .iter()
+        .zip(stats.weekday_rates())
+        .zip(stats.observed_by_weekday)
+        .map(|((day, rate), days)| (day.to_string(), rate, days))
+        .collect();
+    let by_train: Vec<(String, usize)> = stats
+        .by_train
+        .iter()
+        .map(|(train, count)| (format!("Nr {}", train.train_number), *count))
+        .collect();
+    let by_line: Vec<(String, usize)> = stats
+        .by_line
+        .iter()
+        .map(|(id, count)| {
+            let name = lines
+                .by_id(id)
+                .map_or(id.clone(), |line| line.display_name.clone());
+            (name, *count)
+        })
+        .collect();
+    let by_month: Vec<(String, usize)> = stats
+        .by_month
+        .iter()
+        .map(|(month, count)| (month.clone(), *count))
+        .collect();
+
+    rsx! {
+        div { class: "app-header",
+            a { class: "app-header__title", href: "/", "deklassiert" }
+            div { class: "app-header__badge", "Statistik" }
+        }
+
+        main { id: "stats", class: "stats",
+            p { class: "stats-summary",
+                "{stats.total} deklassierte Wagen an {stats.days} Tagen beobachtet."
+            }
+            RateTable { title: "Nach Wochentag, pro beobachtetem Tag", rows: by_weekday }
+            CountTable { title: "Nach Zug", rows: by_train }
+            CountTable { title: "Nach Linie", rows: by_line }
+            CountTable { title: "Nach Monat", rows: by_month }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use opentransportdata::{Confidence, Evn, EvnError, VehicleIdentifier, Verdict};
+
+/// Identifier line, followed by the reason if the coach was flagged.
+pub fn vehicle_tooltip(
+    identifier: &Option<VehicleIdentifier>,
+    deklassiert: &Option<Verdict>,
+) -> Option<String> {
+    let reason = deklassiert.as_ref().map(|verdict| {
+        let confidence = match verdict.confidence {
+            Confidence::High => "sicher",
+            Confidence::Medium => "wahrscheinlich",
+            Confidence::Low => "vermutlich",
+        };
+        format!(
+            "Deklassiert ({}): {}",
+            confidence,
+            verdict.reasons.join(", ")
+        )
+    });
+    match (format_vehicle_identifier(identifier), reason) {
+        (Some(id), Some(reason)) => Some(format!("{}\n{}", id, reason)),
+        (id, reason) => id.or(reason),
+    }
+}
+
+fn format_vehicle_identifier(identifier: &Option<VehicleIdentifier>) -> Option<String> {
+    let id = identifier.as_ref()?;
+    let mut parts: Vec<String> = Vec::new();
+
+    if let Some(name) = id.type_code_name.as_ref() {
+        parts.push(name.clone());
+    }
+    if let (Some(raw), Some(evn)) = (id.evn.as_ref(), id.parsed_evn()) {
+        parts.push(format!("EVN {}", format_evn(raw, evn)));
+    }
+    if let (Some(raw), Some(parent)) = (id.parent_evn.as_ref(), id.parsed_parent_evn()) {
+        parts.push(format!("Parent {}", format_evn(raw, parent)));
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(" · "))
+    }
+}
+
+/// Standard form if the EVN validates, else as sent and marked invalid.
+fn format_evn(raw: &str, evn: Result<Evn, EvnError>) -> String {
+    match evn {
+        Ok(evn) => evn.to_string(),
+        Err(_) => format!("{} (ungültig)", raw),
+    }
+}